@@ -25,7 +25,7 @@ fn main() {
 
     if env::var("VALENCE_REBUILD_SKIP_CIRCUIT").is_err() {
         // circuit
-        // elected domains
+        // default elected domains; the service can override them at startup
 
         let prover = ProverClient::builder().cpu().build();
 
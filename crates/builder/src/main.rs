@@ -1,6 +1,14 @@
+use std::{
+    io::{self, Read as _},
+    path::PathBuf,
+};
+
 use clap::{Parser, Subcommand};
 use tracing_subscriber::{fmt, layer::SubscriberExt as _, util::SubscriberInitExt as _, EnvFilter};
 use valence_coprocessor_client::Client;
+use valence_coprocessor_domain_prover::State;
+
+mod verify;
 
 #[derive(Parser)]
 struct Cli {
@@ -20,6 +28,22 @@ struct Cli {
 pub enum Commands {
     /// Deploys definitions to the co-processor
     Deploy,
+
+    /// Verifies a state JSON, as returned by the service or stored on the co-processor
+    Verify {
+        /// Path to the state JSON. Reads from stdin if omitted.
+        #[arg(value_name = "PATH")]
+        path: Option<PathBuf>,
+
+        /// Prints the report as JSON.
+        #[arg(long)]
+        json: bool,
+
+        /// Directory of rebuilt circuit artifacts, containing the verifying keys. Defaults to
+        /// the keys embedded at build time.
+        #[arg(long, value_name = "DIR")]
+        elf: Option<PathBuf>,
+    },
 }
 
 #[tokio::main]
@@ -27,7 +51,7 @@ async fn main() -> anyhow::Result<()> {
     let Cli { coprocessor, cmd } = Cli::parse();
 
     let filter_layer = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
-    let fmt_layer = fmt::layer().with_target(false);
+    let fmt_layer = fmt::layer().with_target(false).with_writer(io::stderr);
 
     tracing_subscriber::registry()
         .with(filter_layer)
@@ -48,6 +72,33 @@ async fn main() -> anyhow::Result<()> {
 
             println!("{id}");
         }
+
+        Commands::Verify { path, json, elf } => {
+            let state = match path {
+                Some(p) => std::fs::read(p)?,
+                None => {
+                    let mut bytes = Vec::new();
+
+                    io::stdin().read_to_end(&mut bytes)?;
+                    bytes
+                }
+            };
+
+            let state: State = serde_json::from_slice(&state)?;
+            let keys = match elf {
+                Some(elf) => verify::Keys::load(&elf)?,
+                None => verify::Keys::embedded()?,
+            };
+            let report = verify::Report::new(&state, &keys);
+
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                println!("{report}");
+            }
+
+            anyhow::ensure!(report.valid, "state verification failed");
+        }
     }
 
     Ok(())
@@ -0,0 +1,152 @@
+use std::{fmt, fs, path::Path};
+
+use anyhow::Context as _;
+use serde::Serialize;
+use valence_coprocessor_domain_prover::{CircuitOutput, State};
+use valence_coprocessor_domain_prover_verifier::Sp1Verifier;
+
+/// The verifying keys of the circuits.
+pub struct Keys {
+    verifier: Sp1Verifier,
+    inner_vk_b32: Vec<u8>,
+}
+
+impl Keys {
+    /// The keys of the circuits this binary was built with.
+    pub fn embedded() -> anyhow::Result<Self> {
+        let inner_vk = include_bytes!("../../../elf/circuit-vk.bin");
+        let inner_vk_b32 = include_bytes!("../../../elf/circuit-vkh32.bin");
+        let wrapper_vk = include_str!("../../../elf/wrapper-bytes32");
+
+        Self::new(inner_vk, inner_vk_b32.to_vec(), wrapper_vk)
+    }
+
+    /// Reads the keys as written by the build script, so rebuilt circuits are picked up.
+    pub fn load(elf: &Path) -> anyhow::Result<Self> {
+        let read = |name: &str| {
+            let path = elf.join(name);
+
+            fs::read(&path).with_context(|| format!("failed to read `{}`", path.display()))
+        };
+
+        let inner_vk = read("circuit-vk.bin")?;
+        let inner_vk_b32 = read("circuit-vkh32.bin")?;
+        let wrapper_vk = String::from_utf8(read("wrapper-bytes32")?)?;
+
+        Self::new(&inner_vk, inner_vk_b32, &wrapper_vk)
+    }
+
+    fn new(inner_vk: &[u8], inner_vk_b32: Vec<u8>, wrapper_vk: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            verifier: Sp1Verifier::new(inner_vk, wrapper_vk)?,
+            inner_vk_b32,
+        })
+    }
+}
+
+/// The outcome of a single check of the state.
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Check {
+    fn new(name: &'static str, result: anyhow::Result<()>) -> Self {
+        Self {
+            name,
+            ok: result.is_ok(),
+            error: result.err().map(|e| e.to_string()),
+        }
+    }
+}
+
+/// The verification report of a state.
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub uuid: String,
+    pub root: String,
+    pub valid: bool,
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    /// Verifies the proofs of the state against the verifying keys.
+    pub fn new(state: &State, keys: &Keys) -> Self {
+        let checks = vec![
+            Check::new("inner proof", keys.verifier.verify_inner(&state.proof)),
+            Check::new(
+                "wrapper proof",
+                keys.verifier.verify_wrapper(&state.wrapper),
+            ),
+            Check::new("inner vk", inner_vk(state, &keys.inner_vk_b32)),
+            Check::new("public values", public_values(state)),
+            Check::new("root", root(state)),
+        ];
+
+        Self {
+            uuid: hex::encode(state.update.uuid),
+            root: hex::encode(state.update.root),
+            valid: checks.iter().all(|c| c.ok),
+            checks,
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "state `{}` (uuid `{}`)", self.root, self.uuid)?;
+
+        for c in &self.checks {
+            match &c.error {
+                Some(e) => writeln!(f, "  {:<16}FAILED: {e}", c.name)?,
+                None => writeln!(f, "  {:<16}ok", c.name)?,
+            }
+        }
+
+        write!(f, "{}", if self.valid { "valid" } else { "invalid" })
+    }
+}
+
+fn inner_vk(state: &State, vk: &[u8]) -> anyhow::Result<()> {
+    let output = CircuitOutput::decode(&state.proof.decode()?.1)?;
+
+    anyhow::ensure!(
+        output.vk == vk,
+        "unexpected vk `{}`",
+        hex::encode(output.vk)
+    );
+
+    Ok(())
+}
+
+fn public_values(state: &State) -> anyhow::Result<()> {
+    let inner = CircuitOutput::decode(&state.proof.decode()?.1)?;
+    let wrapper = state.wrapper.decode()?.1;
+
+    anyhow::ensure!(
+        CircuitOutput {
+            vk: Vec::new(),
+            ..inner
+        }
+        .encode()
+            == wrapper,
+        "wrapper does not commit to the inner proof outputs"
+    );
+
+    Ok(())
+}
+
+fn root(state: &State) -> anyhow::Result<()> {
+    let root = state.root()?;
+
+    anyhow::ensure!(
+        root == state.update.root,
+        "wrapper commits to `{}`",
+        hex::encode(root)
+    );
+
+    Ok(())
+}
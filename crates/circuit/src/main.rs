@@ -2,40 +2,74 @@
 
 use msgpacker::Unpackable as _;
 use sp1_zkvm::lib::verify::verify_sp1_proof;
-use valence_coprocessor::Hasher as _;
-use valence_coprocessor_domain_prover::{Circuit, CircuitInput};
+use valence_coprocessor::{Hash, Hasher as _};
+use valence_coprocessor_domain_prover::{
+    Circuit, CircuitInput, CircuitOutput, DomainBlock, RecursiveVerifier,
+};
 use valence_coprocessor_sp1::Sp1Hasher;
 use zerocopy::FromBytes;
 
 sp1_zkvm::entrypoint!(main);
 
+/// Verifies light-client proofs provided as deferred SP1 proofs.
+struct Sp1Recursion;
+
+impl RecursiveVerifier for Sp1Recursion {
+    fn verify(vk: &[u32; 8], digest: &Hash) -> anyhow::Result<()> {
+        verify_sp1_proof(vk, digest);
+
+        Ok(())
+    }
+}
+
 pub fn main() {
     let input = sp1_zkvm::io::read_vec();
     let input = CircuitInput::unpack(&input).unwrap().1;
+    let domains = Circuit::domains_commitment::<Sp1Hasher>(&input.domains);
 
     if input.updates.is_empty() {
-        let initial_root = [
-            0xfd, 0xd3, 0x75, 0x61, 0x72, 0x3c, 0xa9, 0x2a, 0x70, 0x33, 0xae, 0xb5, 0x2d, 0xdc,
-            0x02, 0x7d, 0x73, 0x98, 0x04, 0x2b, 0xa9, 0x3b, 0xe3, 0x16, 0xdd, 0x6f, 0x14, 0x14,
-            0x83, 0x95, 0x26, 0x48,
-        ];
-        let output = [&initial_root, input.vk.as_slice()].concat();
-        sp1_zkvm::io::commit_slice(&output);
+        let output = CircuitOutput {
+            root: Circuit::INITIAL_ROOT,
+            domains,
+            strict: input.strict,
+            blocks: Vec::new(),
+            vk: input.vk,
+        };
+        sp1_zkvm::io::commit_slice(&output.encode());
         return;
     }
 
-    let circuit = Circuit::default();
-
     let root = input.initial_root();
     let vkh = <[u32; 8]>::ref_from_bytes(&input.vk).unwrap();
 
-    let digest = [&root[..], input.vk.as_slice()].concat();
-    let digest = Sp1Hasher::hash_raw(&digest);
+    let previous = CircuitOutput::decode(&input.previous).unwrap();
+
+    assert_eq!(previous.root, root);
+    assert_eq!(previous.domains, domains);
+    assert_eq!(previous.vk, input.vk);
+
+    let digest = Sp1Hasher::hash_raw(&input.previous);
 
     verify_sp1_proof(&vkh, &digest);
 
-    let root = circuit.root::<Sp1Hasher>(input.updates).unwrap();
-    let output = [&root[..], input.vk.as_slice()].concat();
+    let circuit = Circuit {
+        initial_root: root,
+        domains: input.domains,
+        strict: input.strict,
+    };
+
+    // the light-client deferred proofs are verified after the recursive one, in update order
+    let summary = circuit
+        .root::<Sp1Hasher, Sp1Recursion>(input.updates)
+        .unwrap();
+    // the chain is strict only if every execution was
+    let output = CircuitOutput {
+        root: summary.root,
+        domains,
+        strict: previous.strict && input.strict,
+        blocks: DomainBlock::merge(&previous.blocks, &summary.blocks),
+        vk: input.vk,
+    };
 
-    sp1_zkvm::io::commit_slice(&output);
+    sp1_zkvm::io::commit_slice(&output.encode());
 }
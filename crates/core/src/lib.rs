@@ -3,8 +3,9 @@
 extern crate alloc;
 
 use alloc::vec::Vec;
+use anyhow::Context as _;
 use msgpacker::{Packable as _, Unpackable as _};
-use sp1_verifier::{Groth16Verifier, GROTH16_VK_BYTES};
+use sp1_verifier::{Groth16Verifier, PlonkVerifier, GROTH16_VK_BYTES, PLONK_VK_BYTES};
 use valence_coprocessor::{Hash, Hasher, HistoricalTransitionProof, Proof, ValidatedBlock};
 
 mod state;
@@ -13,56 +14,494 @@ mod types;
 pub use state::*;
 pub use types::*;
 
+impl Domain {
+    /// Verifies the light-client proof of a block of this domain.
+    pub fn verify<H: Hasher, V: RecursiveVerifier>(
+        &self,
+        number: u64,
+        root: Hash,
+        payload: &[u8],
+    ) -> anyhow::Result<()> {
+        let pi = ValidatedBlock {
+            number,
+            root,
+            payload: Vec::new(),
+        }
+        .pack_to_vec();
+
+        match self.system {
+            ProofSystem::Groth16 => {
+                let proof = Proof::unpack(payload)?.1;
+                let proof = proof.decode()?.0;
+
+                Groth16Verifier::verify(&proof, &pi, &self.vk, &GROTH16_VK_BYTES)?;
+            }
+            ProofSystem::Plonk => {
+                let proof = Proof::unpack(payload)?.1;
+                let proof = proof.decode()?.0;
+
+                PlonkVerifier::verify(&proof, &pi, &self.vk, &PLONK_VK_BYTES)?;
+            }
+            ProofSystem::Sp1Compressed => {
+                let payload = CompressedPayload::unpack(payload)?.1;
+
+                anyhow::ensure!(
+                    payload.proof.decode()?.1 == pi,
+                    "unexpected light-client public values"
+                );
+
+                let vk = const_hex::decode_to_array::<_, 32>(&self.vk)
+                    .map_err(|_| anyhow::anyhow!("invalid recursive vk"))?;
+
+                let mut vkh = [0u32; 8];
+
+                vk.chunks_exact(4)
+                    .zip(vkh.iter_mut())
+                    .for_each(|(b, w)| *w = u32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+
+                V::verify(&vkh, &H::hash_raw(&pi))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
 impl Circuit {
-    pub fn root<H: Hasher>(&self, updates: Vec<HistoricalTransitionProof>) -> anyhow::Result<Hash> {
+    pub fn root<H: Hasher, V: RecursiveVerifier>(
+        &self,
+        updates: Vec<HistoricalTransitionProof>,
+    ) -> anyhow::Result<CircuitSummary> {
         let mut root = updates
             .first()
             .map(|u| u.update.previous)
             .unwrap_or_default();
+        let mut blocks = Vec::new();
 
-        for proof in updates {
-            let update = proof.verify::<H>()?;
+        for (index, proof) in updates.into_iter().enumerate() {
+            let domain = proof.update.block.domain;
+            let block = self
+                .apply::<H, V>(&mut root, proof)
+                .context(UpdateFailure { index, domain })?;
 
-            anyhow::ensure!(root == update.previous, "unexpected root");
+            blocks.extend(block);
+        }
 
-            root = update.root;
+        let blocks = DomainBlock::merge(&[], &blocks);
 
-            let id = self
-                .domains
-                .iter()
-                .enumerate()
-                .find_map(|(i, d)| (d.id == update.block.domain).then_some(i));
+        Ok(CircuitSummary { root, blocks })
+    }
 
-            // won't verify lightclient proof if domain not elected
-            let id = match id {
-                Some(id) => id,
-                None => continue,
-            };
+    /// Applies an update on top of `root`, returning the verified block if its domain is elected.
+    fn apply<H: Hasher, V: RecursiveVerifier>(
+        &self,
+        root: &mut Hash,
+        proof: HistoricalTransitionProof,
+    ) -> anyhow::Result<Option<DomainBlock>> {
+        let update = proof.verify::<H>()?;
 
-            let pi = ValidatedBlock {
-                number: update.block.number,
-                root: update.block.root,
-                payload: Vec::new(),
-            }
-            .pack_to_vec();
+        anyhow::ensure!(*root == update.previous, "unexpected root");
 
-            let proof = Proof::unpack(&update.block.payload)?.1;
-            let proof = proof.decode()?.0;
+        *root = update.root;
 
-            Groth16Verifier::verify(&proof, &pi, &self.domains[id].vk, &GROTH16_VK_BYTES)?;
-        }
+        let id = self
+            .domains
+            .iter()
+            .enumerate()
+            .find_map(|(i, d)| (d.id == update.block.domain).then_some(i));
+
+        // won't verify lightclient proof if domain not elected, unless strict
+        let id = match id {
+            Some(id) => id,
+            None if self.strict => anyhow::bail!("domain not elected"),
+            None => return Ok(None),
+        };
+
+        self.domains[id].verify::<H, V>(
+            update.block.number,
+            update.block.root,
+            &update.block.payload,
+        )?;
+
+        Ok(Some(DomainBlock {
+            id: update.block.domain,
+            number: update.block.number,
+            root: update.block.root,
+        }))
+    }
+}
+
+#[cfg(test)]
+struct AcceptRecursion;
 
-        Ok(root)
+#[cfg(test)]
+impl RecursiveVerifier for AcceptRecursion {
+    fn verify(_vk: &[u32; 8], _digest: &Hash) -> anyhow::Result<()> {
+        Ok(())
     }
 }
 
 #[test]
-fn circuit_root_works() {
+fn domain_system_defaults_to_groth16() {
+    let domains = include_bytes!("../../../elf/domains.json");
+    let domains: Vec<Domain> = serde_json::from_slice(&domains[..]).unwrap();
+
+    assert!(domains.iter().all(|d| d.system == ProofSystem::Groth16));
+}
+
+#[cfg(test)]
+fn malformed_payload() -> Vec<u8> {
+    Proof::new(alloc::vec![0; 8], Vec::new()).pack_to_vec()
+}
+
+#[test]
+fn domain_verify_dispatches_groth16() {
     use valence_coprocessor_sp1::Sp1Hasher;
 
-    let input = include_bytes!("../../../assets/input.json");
-    let CircuitInput { updates, .. } = serde_json::from_slice(input).unwrap();
-    let circuit = Circuit::default();
+    let domain = Circuit::default().domains[0].clone();
+    let error = domain
+        .verify::<Sp1Hasher, AcceptRecursion>(1, Hash::default(), &malformed_payload())
+        .unwrap_err();
+
+    assert!(error.downcast_ref::<sp1_verifier::Groth16Error>().is_some());
+    assert!(domain
+        .verify::<Sp1Hasher, AcceptRecursion>(1, Hash::default(), &[])
+        .is_err());
+}
+
+#[test]
+fn domain_verify_dispatches_plonk() {
+    use valence_coprocessor_sp1::Sp1Hasher;
+
+    let domain = Domain {
+        system: ProofSystem::Plonk,
+        ..Circuit::default().domains[0].clone()
+    };
+    let error = domain
+        .verify::<Sp1Hasher, AcceptRecursion>(1, Hash::default(), &malformed_payload())
+        .unwrap_err();
+
+    assert!(error.downcast_ref::<sp1_verifier::PlonkError>().is_some());
+}
+
+#[test]
+fn domain_verify_dispatches_recursive() {
+    use valence_coprocessor_sp1::Sp1Hasher;
+
+    let domain = Domain {
+        system: ProofSystem::Sp1Compressed,
+        ..Circuit::default().domains[0].clone()
+    };
+    let payload = |number: u64| {
+        let pi = ValidatedBlock {
+            number,
+            root: Hash::default(),
+            payload: Vec::new(),
+        }
+        .pack_to_vec();
+
+        CompressedPayload {
+            proof: Proof::new(alloc::vec![0; 8], pi),
+            vk: Vec::new(),
+        }
+        .pack_to_vec()
+    };
+
+    domain
+        .verify::<Sp1Hasher, AcceptRecursion>(1, Hash::default(), &payload(1))
+        .unwrap();
+
+    assert!(domain
+        .verify::<Sp1Hasher, NoRecursion>(1, Hash::default(), &payload(1))
+        .is_err());
+
+    // the light-client proof must commit to the verified block
+    assert!(domain
+        .verify::<Sp1Hasher, AcceptRecursion>(1, Hash::default(), &payload(2))
+        .is_err());
+    assert!(domain
+        .verify::<Sp1Hasher, AcceptRecursion>(1, Hash::default(), &malformed_payload())
+        .is_err());
+
+    let domain = Domain {
+        vk: "0xzz".into(),
+        ..domain
+    };
+
+    assert!(domain
+        .verify::<Sp1Hasher, AcceptRecursion>(1, Hash::default(), &payload(1))
+        .is_err());
+}
+
+#[test]
+fn circuit_output_roundtrip() {
+    let output = CircuitOutput {
+        root: [1; 32],
+        domains: [2; 32],
+        strict: true,
+        blocks: alloc::vec![
+            DomainBlock {
+                id: [3; 32],
+                number: 10,
+                root: [4; 32],
+            },
+            DomainBlock {
+                id: [5; 32],
+                number: 20,
+                root: [6; 32],
+            },
+        ],
+        vk: include_bytes!("../../../elf/circuit-vkh32.bin").to_vec(),
+    };
+
+    let bytes = output.encode();
+    let (wrapper, vk) = bytes.split_at(bytes.len() - 32);
+
+    assert_eq!(CircuitOutput::decode(&bytes).unwrap(), output);
+    assert_eq!(vk, output.vk.as_slice());
+
+    let wrapper = WrapperOutput::decode(wrapper).unwrap();
+
+    assert_eq!(wrapper.root, output.root);
+    assert_eq!(wrapper.domains, output.domains);
+    assert!(wrapper.strict);
+    assert_eq!(wrapper.blocks, output.blocks);
+}
+
+#[test]
+fn legacy_outputs_are_decoded() {
+    let vk = include_bytes!("../../../elf/circuit-vkh32.bin");
+    let inner = [[1; 32].as_slice(), vk.as_slice()].concat();
+    let output = CircuitOutput::decode(&inner).unwrap();
+
+    assert_eq!(output.root, [1; 32]);
+    assert_eq!(output.domains, [0; 32]);
+    assert!(!output.strict);
+    assert!(output.blocks.is_empty());
+    assert_eq!(output.vk, vk.as_slice());
+
+    let wrapper = WrapperOutput::decode(&[1; 32]).unwrap();
+
+    assert_eq!(wrapper.root, [1; 32]);
+    assert!(wrapper.blocks.is_empty());
+
+    assert!(WrapperOutput::decode(&[1; 33]).is_err());
+    assert!(CircuitOutput::decode(&[1; 65]).is_err());
+}
+
+#[test]
+fn domain_blocks_merge_keeps_latest() {
+    let block = |id: u8, number: u64| DomainBlock {
+        id: [id; 32],
+        number,
+        root: [number as u8; 32],
+    };
+
+    let previous = [block(2, 5), block(1, 7)];
+    let latest = [block(1, 6), block(2, 8), block(3, 1)];
+
+    assert_eq!(
+        DomainBlock::merge(&previous, &latest),
+        alloc::vec![block(1, 7), block(2, 8), block(3, 1)]
+    );
+}
+
+#[cfg(test)]
+fn test_state(uuid: u128, wrapped_at: u64) -> State {
+    State {
+        update: valence_coprocessor::HistoricalUpdate {
+            uuid: uuid.to_be_bytes(),
+            ..Default::default()
+        },
+        proof: Proof::new(Vec::new(), Vec::new()),
+        wrapper: Proof::new(Vec::new(), Vec::new()),
+        metadata: StateMetadata {
+            wrapped_at,
+            height: uuid as u64,
+            ..Default::default()
+        },
+    }
+}
+
+#[test]
+fn service_state_set_capacity_evicts_oldest() {
+    let mut service = ServiceState::default().with_capacity(4);
+
+    for uuid in 1..=4 {
+        service.insert(test_state(uuid, 0));
+    }
+
+    let evicted = service.set_capacity(2);
+
+    assert_eq!(evicted, alloc::vec![test_state(1, 0), test_state(2, 0)]);
+    assert_eq!(service.len(), 2);
+    assert_eq!(service.remove(&3u128.to_be_bytes()), Some(test_state(3, 0)));
+    assert_eq!(service.latest(), Some(&test_state(4, 0)));
+    assert!(service.get(&3u128.to_be_bytes()).is_none());
+}
+
+#[cfg(test)]
+fn lower_bound_distance(service: &ServiceState, uuid: u128) -> Option<u128> {
+    service
+        .get_lower_bound(uuid.to_be_bytes())
+        .map(|s| uuid - u128::from_be_bytes(s.update.uuid))
+}
+
+#[test]
+fn service_state_latest_policy_keeps_latest() {
+    let mut service = ServiceState::default().with_capacity(10);
+
+    for uuid in 1..=200 {
+        service.insert(test_state(uuid, 0));
+    }
+
+    assert_eq!(service.len(), 10);
+    assert_eq!(lower_bound_distance(&service, 195), Some(0));
+    assert_eq!(lower_bound_distance(&service, 190), None);
+    assert_eq!(lower_bound_distance(&service, 1), None);
+}
+
+#[test]
+fn service_state_logarithmic_policy_bounds_distance() {
+    let mut service = ServiceState::default()
+        .with_capacity(10)
+        .with_policy(EvictionPolicy::Logarithmic);
+
+    for uuid in 1..=200 {
+        service.insert(test_state(uuid, 0));
+    }
+
+    assert_eq!(service.len(), 10);
+    assert_eq!(service.iter().next(), Some(&test_state(1, 0)));
+    assert_eq!(service.latest(), Some(&test_state(200, 0)));
+
+    // the distance to the lower bound never exceeds the age of the root
+    for uuid in 1..=200 {
+        let distance = lower_bound_distance(&service, uuid).unwrap();
+
+        assert!(distance <= 200 - uuid, "{uuid} is {distance} away");
+    }
+
+    // the recent roots have closer lower bounds than the old ones
+    assert!(lower_bound_distance(&service, 199).unwrap() <= 1);
+
+    // the checkpoints are exponentially spaced: the gaps shrink towards the latest, and the
+    // distance to the latest at least doubles every other checkpoint
+    let distances: Vec<_> = service
+        .iter()
+        .skip(1)
+        .map(|s| 200 - u128::from_be_bytes(s.update.uuid))
+        .collect();
+
+    assert!(distances.windows(3).all(|w| w[0] - w[1] >= w[1] - w[2]));
+    assert!(distances.windows(3).all(|w| w[0] >= 2 * w[2]));
+
+    // switching the policy evicts the checkpoints
+    let evicted = service.set_policy(EvictionPolicy::Latest);
+
+    assert!(evicted.is_empty());
+    assert_eq!(service.set_capacity(2).len(), 8);
+    assert_eq!(lower_bound_distance(&service, 150), None);
+}
+
+#[test]
+fn service_state_logarithmic_policy_survives_reloads() {
+    let policy = EvictionPolicy::Logarithmic;
+    let mut service = ServiceState::default()
+        .with_capacity(10)
+        .with_policy(policy);
+
+    for uuid in 1..=200 {
+        service.insert(test_state(uuid, 0));
+    }
+
+    // a restarted service inserts the stored states into an empty one
+    let mut reloaded = ServiceState::default()
+        .with_capacity(10)
+        .with_policy(policy);
+
+    for state in service.iter() {
+        reloaded.insert(state.clone());
+    }
+
+    for uuid in 201..=250 {
+        service.insert(test_state(uuid, 0));
+        reloaded.insert(test_state(uuid, 0));
+    }
+
+    assert_eq!(reloaded, service);
+}
+
+#[test]
+fn service_state_ttl_policy_evicts_expired() {
+    let mut service = ServiceState::default()
+        .with_capacity(10)
+        .with_policy(EvictionPolicy::Ttl(25));
+
+    for uuid in 1..=5 {
+        service.insert(test_state(uuid, uuid as u64 * 10));
+    }
+
+    assert_eq!(service.len(), 3);
+    assert_eq!(lower_bound_distance(&service, 2), None);
+    assert_eq!(lower_bound_distance(&service, 4), Some(0));
+
+    // expired states are not retained, even if below capacity
+    service.insert(test_state(2, 20));
+
+    assert_eq!(service.len(), 3);
+    assert_eq!(lower_bound_distance(&service, 2), None);
+
+    // the capacity still applies to the unexpired states
+    for uuid in 6..=20 {
+        service.insert(test_state(uuid, 100));
+    }
+
+    assert_eq!(service.len(), 10);
+    assert_eq!(lower_bound_distance(&service, 10), None);
+    assert_eq!(lower_bound_distance(&service, 11), Some(0));
+
+    // states without a wrapper timestamp only expire by capacity
+    let mut service = ServiceState::default()
+        .with_capacity(10)
+        .with_policy(EvictionPolicy::Ttl(25));
+
+    service.insert(test_state(1, 0));
+    service.insert(test_state(2, 100));
+
+    assert_eq!(service.len(), 2);
+    assert_eq!(lower_bound_distance(&service, 1), Some(0));
+}
+
+#[test]
+fn service_state_range_queries() {
+    let mut service = ServiceState::default().with_capacity(10);
+
+    for uuid in [2, 4, 6, 8] {
+        service.insert(test_state(uuid, 0));
+    }
+
+    let uuids = |states: Vec<&State>| {
+        states
+            .iter()
+            .map(|s| u128::from_be_bytes(s.update.uuid))
+            .collect::<Vec<_>>()
+    };
+
+    let from = 3u128.to_be_bytes();
+    let to = 8u128.to_be_bytes();
+
+    assert_eq!(uuids(service.range(from..to).collect()), [4, 6]);
+    assert_eq!(uuids(service.range(from..=to).collect()), [4, 6, 8]);
+    assert_eq!(uuids(service.range(..).rev().collect()), [8, 6, 4, 2]);
+    assert_eq!(uuids(service.latest_n(3).collect()), [8, 6, 4]);
+    assert_eq!(uuids(service.latest_n(10).collect()), [8, 6, 4, 2]);
+
+    assert_eq!(service.get_upper_bound(from), Some(&test_state(4, 0)));
+    assert_eq!(service.get_upper_bound(to), Some(&test_state(8, 0)));
+    assert_eq!(service.get_upper_bound(9u128.to_be_bytes()), None);
+    assert_eq!(service.get_lower_bound(from), Some(&test_state(2, 0)));
 
-    circuit.root::<Sp1Hasher>(updates).unwrap();
+    assert!(service.contains(&6u128.to_be_bytes()));
+    assert!(!service.contains(&5u128.to_be_bytes()));
 }
@@ -1,10 +1,15 @@
-use core::{cmp, ops::Bound};
+use core::{
+    cmp,
+    ops::{Bound, RangeBounds},
+};
 
-use alloc::collections::btree_map::BTreeMap;
+use alloc::{collections::btree_map::BTreeMap, vec::Vec};
 use msgpacker::MsgPacker;
 use serde::{Deserialize, Serialize};
 use valence_coprocessor::{Hash, HistoricalUpdate, Proof};
 
+use crate::{DomainStats, WrapperOutput};
+
 /// A controller state.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, MsgPacker)]
 pub struct State {
@@ -16,6 +21,31 @@ pub struct State {
 
     /// The latest computed wrapper.
     pub wrapper: Proof,
+
+    /// Metadata of the execution that produced the proof.
+    #[serde(default)]
+    pub metadata: StateMetadata,
+}
+
+/// Metadata of the circuit execution that produced a state.
+#[derive(
+    Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, MsgPacker,
+)]
+pub struct StateMetadata {
+    /// Per-domain counters of verified and skipped blocks.
+    pub domains: Vec<DomainStats>,
+
+    /// Unix timestamp, in seconds, of the inner proof.
+    #[serde(default)]
+    pub proved_at: u64,
+
+    /// Unix timestamp, in seconds, of the wrapper proof.
+    #[serde(default)]
+    pub wrapped_at: u64,
+
+    /// Historical updates applied since the initial root.
+    #[serde(default)]
+    pub height: u64,
 }
 
 impl PartialOrd for State {
@@ -47,18 +77,54 @@ impl State {
 
     /// Returns the root of the update.
     pub fn root(&self) -> anyhow::Result<Hash> {
+        Ok(self.output()?.root)
+    }
+
+    /// Returns the commitment of the domains enforced by the proof.
+    pub fn domains(&self) -> anyhow::Result<Hash> {
+        Ok(self.output()?.domains)
+    }
+
+    /// Returns the decoded public values of the wrapper proof.
+    pub fn output(&self) -> anyhow::Result<WrapperOutput> {
         let inputs = self.wrapper.decode()?.1;
 
-        Ok(Hash::try_from(&inputs[..32])?)
+        WrapperOutput::decode(&inputs)
     }
 }
 
+/// The retention policy of a `ServiceState` at capacity.
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, MsgPacker,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum EvictionPolicy {
+    /// Keeps the latest states, evicting the oldest first.
+    #[default]
+    Latest,
+
+    /// Keeps exponentially spaced checkpoints, so any historical root has a lower bound at a
+    /// distance proportional to its age.
+    ///
+    /// Distances are measured in historical updates, by the state height.
+    Logarithmic,
+
+    /// Evicts the states wrapped more than the provided seconds before the latest state, then
+    /// the oldest first.
+    ///
+    /// States without a wrapper timestamp, as loaded from the co-processor, only expire by
+    /// capacity.
+    Ttl(u64),
+}
+
 #[derive(
     Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, MsgPacker,
 )]
 pub struct ServiceState {
     items: BTreeMap<[u8; 16], State>,
     capacity: usize,
+    #[serde(default)]
+    policy: EvictionPolicy,
 }
 
 impl ServiceState {
@@ -67,18 +133,121 @@ impl ServiceState {
         self
     }
 
+    pub fn with_policy(mut self, policy: EvictionPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn policy(&self) -> EvictionPolicy {
+        self.policy
+    }
+
+    /// Updates the capacity, evicting the states in excess.
+    pub fn set_capacity(&mut self, capacity: usize) -> Vec<State> {
+        self.capacity = capacity.max(1);
+        self.evict()
+    }
+
+    /// Updates the retention policy, evicting the states it rejects.
+    pub fn set_policy(&mut self, policy: EvictionPolicy) -> Vec<State> {
+        self.policy = policy;
+        self.evict()
+    }
+
+    /// Inserts the state, evicting according to the retention policy.
+    ///
+    /// The inserted state itself might be evicted.
     pub fn insert(&mut self, state: State) {
-        if let Some(lower) = self.items.iter().next() {
-            if &state < lower.1 && self.items.len() >= self.capacity {
-                return;
-            }
+        self.items.insert(state.update.uuid, state);
+        self.evict();
+    }
+
+    fn evict(&mut self) -> Vec<State> {
+        let mut evicted = Vec::new();
+
+        if let EvictionPolicy::Ttl(ttl) = self.policy {
+            let latest = self.latest().map(|s| s.metadata.wrapped_at).unwrap_or(0);
+            let expired: Vec<_> = self
+                .items
+                .iter()
+                .rev()
+                .skip(1)
+                .filter(|(_, s)| s.metadata.wrapped_at > 0)
+                .filter(|(_, s)| latest.saturating_sub(s.metadata.wrapped_at) > ttl)
+                .map(|(uuid, _)| *uuid)
+                .collect();
+
+            evicted.extend(expired.iter().filter_map(|uuid| self.remove(uuid)));
         }
 
-        while self.items.len() >= self.capacity {
-            self.items.pop_first();
+        while self.items.len() > self.capacity {
+            let uuid = match self.policy {
+                EvictionPolicy::Logarithmic => self.sparsest(),
+                _ => None,
+            };
+
+            let state = uuid
+                .or_else(|| self.items.keys().next().copied())
+                .and_then(|uuid| self.remove(&uuid));
+
+            evicted.extend(state);
         }
 
-        self.items.insert(state.update.uuid, state);
+        evicted.sort();
+        evicted
+    }
+
+    /// Returns the state whose eviction opens the smallest gap relative to its distance to the
+    /// latest state.
+    fn sparsest(&self) -> Option<[u8; 16]> {
+        let heights: Vec<_> = self
+            .items
+            .values()
+            .map(|s| s.metadata.height as f64)
+            .collect();
+        let latest = *heights.last()?;
+
+        self.items
+            .keys()
+            .skip(1)
+            .zip(heights.windows(3))
+            .map(|(uuid, w)| (uuid, (w[2] - w[0]) / (latest - w[2] + 1.0)))
+            .fold(
+                None,
+                |min: Option<(&[u8; 16], f64)>, (uuid, gap)| match min {
+                    Some((_, m)) if m <= gap => min,
+                    _ => Some((uuid, gap)),
+                },
+            )
+            .map(|(uuid, _)| *uuid)
+    }
+
+    pub fn remove(&mut self, uuid: &[u8; 16]) -> Option<State> {
+        self.items.remove(uuid)
+    }
+
+    pub fn get(&self, uuid: &[u8; 16]) -> Option<&State> {
+        self.items.get(uuid)
+    }
+
+    pub fn contains(&self, uuid: &[u8; 16]) -> bool {
+        self.items.contains_key(uuid)
+    }
+
+    pub fn get_by_root(&self, root: &Hash) -> Option<&State> {
+        self.items.values().find(|s| &s.update.root == root)
     }
 
     pub fn get_lower_bound(&self, uuid: [u8; 16]) -> Option<&State> {
@@ -88,7 +257,29 @@ impl ServiceState {
         self.items.range((lower, upper)).next_back().map(|(_, s)| s)
     }
 
+    /// Returns the oldest state with a UUID greater than or equal to the provided one.
+    pub fn get_upper_bound(&self, uuid: [u8; 16]) -> Option<&State> {
+        self.items.range(uuid..).next().map(|(_, s)| s)
+    }
+
+    /// Returns the states with a UUID within the range, oldest first.
+    pub fn range<R>(&self, range: R) -> impl DoubleEndedIterator<Item = &State>
+    where
+        R: RangeBounds<[u8; 16]>,
+    {
+        self.items.range(range).map(|(_, s)| s)
+    }
+
+    /// Returns up to `count` states, latest first.
+    pub fn latest_n(&self, count: usize) -> impl Iterator<Item = &State> {
+        self.items.values().rev().take(count)
+    }
+
     pub fn latest(&self) -> Option<&State> {
         self.items.iter().next_back().map(|(_, s)| s)
     }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &State> {
+        self.items.values()
+    }
 }
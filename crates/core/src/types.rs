@@ -1,7 +1,39 @@
-use alloc::{string::String, vec::Vec};
-use msgpacker::MsgPacker;
+use core::fmt;
+
+use alloc::{collections::btree_map::BTreeMap, string::String, vec::Vec};
+use msgpacker::{MsgPacker, Packable as _};
 use serde::{Deserialize, Serialize};
-use valence_coprocessor::{Hash, HistoricalTransitionProof};
+use valence_coprocessor::{Hash, Hasher, HistoricalTransitionProof, Proof};
+
+/// The proof system of a domain light-client.
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, MsgPacker,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum ProofSystem {
+    /// SP1 Groth16 proof, verified against the domain vk bytes32.
+    #[default]
+    Groth16,
+
+    /// SP1 PLONK proof, verified against the domain vk bytes32.
+    Plonk,
+
+    /// SP1 compressed proof, verified recursively against the domain vk hash (u32 words, LE).
+    ///
+    /// The block payload is a [CompressedPayload]; its proof is provided to the zkVM as a deferred
+    /// proof.
+    Sp1Compressed,
+}
+
+/// The block payload of a [ProofSystem::Sp1Compressed] domain.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, MsgPacker)]
+pub struct CompressedPayload {
+    /// The compressed light-client proof, committing to the validated block.
+    pub proof: Proof,
+
+    /// The CBOR encoded SP1 verifying key of the light-client program.
+    pub vk: Vec<u8>,
+}
 
 /// An elected domain for verification.
 #[derive(
@@ -10,6 +42,26 @@ use valence_coprocessor::{Hash, HistoricalTransitionProof};
 pub struct Domain {
     pub id: Hash,
     pub vk: String,
+
+    #[serde(default)]
+    pub system: ProofSystem,
+}
+
+/// A verifier of recursive SP1 proofs.
+pub trait RecursiveVerifier {
+    /// Verifies a proof of the program `vk` that committed public values with the provided
+    /// SHA-256 digest.
+    fn verify(vk: &[u32; 8], digest: &Hash) -> anyhow::Result<()>;
+}
+
+/// A recursive verifier for environments without SP1 recursion; rejects every proof.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoRecursion;
+
+impl RecursiveVerifier for NoRecursion {
+    fn verify(_vk: &[u32; 8], _digest: &Hash) -> anyhow::Result<()> {
+        anyhow::bail!("recursive verification is not supported")
+    }
 }
 
 /// A circuit definition.
@@ -17,6 +69,9 @@ pub struct Domain {
 pub struct Circuit {
     pub initial_root: Hash,
     pub domains: Vec<Domain>,
+
+    /// Rejects updates of domains that are not elected.
+    pub strict: bool,
 }
 
 impl Default for Circuit {
@@ -27,15 +82,85 @@ impl Default for Circuit {
         Self {
             initial_root: Hash::default(),
             domains,
+            strict: false,
         }
     }
 }
 
+impl Circuit {
+    /// The historical root committed by the bootstrap proof.
+    pub const INITIAL_ROOT: Hash = [
+        0xfd, 0xd3, 0x75, 0x61, 0x72, 0x3c, 0xa9, 0x2a, 0x70, 0x33, 0xae, 0xb5, 0x2d, 0xdc, 0x02,
+        0x7d, 0x73, 0x98, 0x04, 0x2b, 0xa9, 0x3b, 0xe3, 0x16, 0xdd, 0x6f, 0x14, 0x14, 0x83, 0x95,
+        0x26, 0x48,
+    ];
+
+    /// Computes the commitment of an elected domain set.
+    ///
+    /// The domains are sorted prior to hashing so the commitment is independent of the order
+    /// they were configured.
+    pub fn domains_commitment<H: Hasher>(domains: &[Domain]) -> Hash {
+        let mut domains = domains.to_vec();
+
+        domains.sort();
+
+        H::hash(&domains.pack_to_vec())
+    }
+
+    /// Counts the blocks of each domain that will be verified or skipped for the given updates.
+    pub fn stats(&self, updates: &[HistoricalTransitionProof]) -> Vec<DomainStats> {
+        let mut stats: BTreeMap<Hash, DomainStats> = BTreeMap::new();
+
+        for u in updates {
+            let id = u.update.block.domain;
+            let entry = stats.entry(id).or_insert_with(|| DomainStats {
+                id,
+                ..Default::default()
+            });
+
+            if self.domains.iter().any(|d| d.id == id) {
+                entry.verified += 1;
+            } else {
+                entry.skipped += 1;
+            }
+        }
+
+        stats.into_values().collect()
+    }
+}
+
+/// Counters of the blocks of a domain processed by a circuit execution.
+#[derive(
+    Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, MsgPacker,
+)]
+pub struct DomainStats {
+    /// The domain id.
+    pub id: Hash,
+
+    /// Blocks with a verified light-client proof.
+    pub verified: u64,
+
+    /// Blocks accepted without verification as the domain is not elected.
+    pub skipped: u64,
+}
+
 /// The input of a circuit execution.
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, MsgPacker)]
 pub struct CircuitInput {
     pub vk: Vec<u8>,
     pub updates: Vec<HistoricalTransitionProof>,
+
+    /// The elected domains enforced by this execution.
+    #[serde(default)]
+    pub domains: Vec<Domain>,
+
+    /// Rejects updates of domains that are not elected.
+    #[serde(default)]
+    pub strict: bool,
+
+    /// The public values committed by the recursive proof.
+    #[serde(default)]
+    pub previous: Vec<u8>,
 }
 
 impl Default for CircuitInput {
@@ -43,6 +168,9 @@ impl Default for CircuitInput {
         Self {
             vk: include_bytes!("../../../elf/circuit-vkh32.bin").to_vec(),
             updates: Default::default(),
+            domains: Circuit::default().domains,
+            strict: false,
+            previous: Vec::new(),
         }
     }
 }
@@ -55,3 +183,226 @@ impl CircuitInput {
             .unwrap_or_default()
     }
 }
+
+/// The latest verified block of an elected domain.
+#[derive(
+    Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, MsgPacker,
+)]
+pub struct DomainBlock {
+    /// The domain id.
+    pub id: Hash,
+
+    /// The block number.
+    pub number: u64,
+
+    /// The block root.
+    pub root: Hash,
+}
+
+impl DomainBlock {
+    /// Length of an encoded block.
+    pub const LEN: usize = 72;
+
+    /// Encodes the block as `id || number (BE) || root`.
+    pub fn encode(&self) -> [u8; Self::LEN] {
+        let mut bytes = [0u8; Self::LEN];
+
+        bytes[..32].copy_from_slice(&self.id);
+        bytes[32..40].copy_from_slice(&self.number.to_be_bytes());
+        bytes[40..].copy_from_slice(&self.root);
+
+        bytes
+    }
+
+    /// Decodes a sequence of encoded blocks.
+    pub fn decode_all(bytes: &[u8]) -> anyhow::Result<Vec<Self>> {
+        anyhow::ensure!(bytes.len() % Self::LEN == 0, "invalid domain blocks length");
+
+        bytes
+            .chunks_exact(Self::LEN)
+            .map(|b| -> anyhow::Result<Self> {
+                Ok(Self {
+                    id: Hash::try_from(&b[..32])?,
+                    number: u64::from_be_bytes(<[u8; 8]>::try_from(&b[32..40])?),
+                    root: Hash::try_from(&b[40..])?,
+                })
+            })
+            .collect()
+    }
+
+    /// Merges two block sets, keeping the highest block of each domain, sorted by id.
+    pub fn merge(previous: &[Self], latest: &[Self]) -> Vec<Self> {
+        let mut blocks: BTreeMap<Hash, Self> = BTreeMap::new();
+
+        for b in previous.iter().chain(latest) {
+            match blocks.get(&b.id) {
+                Some(c) if c.number >= b.number => (),
+                _ => {
+                    blocks.insert(b.id, b.clone());
+                }
+            }
+        }
+
+        blocks.into_values().collect()
+    }
+}
+
+/// The result of a circuit execution.
+#[derive(
+    Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, MsgPacker,
+)]
+pub struct CircuitSummary {
+    /// The computed historical root.
+    pub root: Hash,
+
+    /// The latest verified block of each elected domain, sorted by id.
+    pub blocks: Vec<DomainBlock>,
+}
+
+/// The historical update rejected by a circuit execution, attached as context to the error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UpdateFailure {
+    /// Position of the update in the batch.
+    pub index: usize,
+
+    /// Domain of the update block.
+    pub domain: Hash,
+}
+
+impl fmt::Display for UpdateFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "update {} of domain `", self.index)?;
+
+        for b in &self.domain {
+            write!(f, "{b:02x}")?;
+        }
+
+        f.write_str("`")
+    }
+}
+
+/// The public values committed by the inner circuit.
+///
+/// Encoded as `root || domains || strict || blocks || vk`. The legacy `root || vk` encoding is
+/// decoded with no domains nor blocks.
+#[derive(
+    Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, MsgPacker,
+)]
+pub struct CircuitOutput {
+    /// The historical root.
+    pub root: Hash,
+
+    /// The commitment of the enforced domains.
+    pub domains: Hash,
+
+    /// Whether updates of non-elected domains were rejected since the bootstrap.
+    pub strict: bool,
+
+    /// The latest verified block of each elected domain, sorted by id.
+    pub blocks: Vec<DomainBlock>,
+
+    /// The inner circuit verifying key hash.
+    pub vk: Vec<u8>,
+}
+
+impl CircuitOutput {
+    /// Length of the legacy `root || vk` encoding.
+    pub const LEGACY_LEN: usize = 64;
+
+    /// Encodes the output as committed by the circuit.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = encode_output(&self.root, &self.domains, self.strict, &self.blocks);
+
+        bytes.extend_from_slice(&self.vk);
+        bytes
+    }
+
+    /// Decodes the public values of an inner proof.
+    pub fn decode(bytes: &[u8]) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            bytes.len() == Self::LEGACY_LEN || bytes.len() >= 97,
+            "invalid circuit output length"
+        );
+
+        let (output, vk) = bytes.split_at(bytes.len() - 32);
+        let WrapperOutput {
+            root,
+            domains,
+            strict,
+            blocks,
+        } = WrapperOutput::decode(output)?;
+
+        Ok(Self {
+            root,
+            domains,
+            strict,
+            blocks,
+            vk: vk.to_vec(),
+        })
+    }
+}
+
+/// The public values committed by the wrapper circuit; the inner output without its vk.
+///
+/// Encoded as `root || domains || strict || blocks`, where each block is
+/// `id || number (u64 BE) || root`. The legacy `root` encoding is decoded with no domains nor
+/// blocks.
+#[derive(
+    Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, MsgPacker,
+)]
+pub struct WrapperOutput {
+    /// The historical root.
+    pub root: Hash,
+
+    /// The commitment of the enforced domains.
+    pub domains: Hash,
+
+    /// Whether updates of non-elected domains were rejected since the bootstrap.
+    pub strict: bool,
+
+    /// The latest verified block of each elected domain, sorted by id.
+    pub blocks: Vec<DomainBlock>,
+}
+
+impl WrapperOutput {
+    /// Length of the legacy `root` encoding.
+    pub const LEGACY_LEN: usize = 32;
+
+    /// Encodes the output as committed by the wrapper.
+    pub fn encode(&self) -> Vec<u8> {
+        encode_output(&self.root, &self.domains, self.strict, &self.blocks)
+    }
+
+    /// Decodes the public values of a wrapper proof.
+    pub fn decode(bytes: &[u8]) -> anyhow::Result<Self> {
+        if bytes.len() == Self::LEGACY_LEN {
+            return Ok(Self {
+                root: Hash::try_from(bytes)?,
+                ..Default::default()
+            });
+        }
+
+        anyhow::ensure!(bytes.len() >= 65, "invalid wrapper output length");
+
+        Ok(Self {
+            root: Hash::try_from(&bytes[..32])?,
+            domains: Hash::try_from(&bytes[32..64])?,
+            strict: bytes[64] != 0,
+            blocks: DomainBlock::decode_all(&bytes[65..])?,
+        })
+    }
+}
+
+fn encode_output(root: &Hash, domains: &Hash, strict: bool, blocks: &[DomainBlock]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(65 + blocks.len() * DomainBlock::LEN + 32);
+
+    bytes.extend_from_slice(root);
+    bytes.extend_from_slice(domains);
+    bytes.push(strict as u8);
+
+    for b in blocks {
+        bytes.extend_from_slice(&b.encode());
+    }
+
+    bytes
+}
@@ -0,0 +1,104 @@
+//! Circuit executions over recorded co-processor inputs, under `assets/`.
+//!
+//! The fixtures are read at runtime; run with `--ignored` once they are present.
+
+use valence_coprocessor_domain_prover::{
+    Circuit, CircuitInput, NoRecursion, ProofSystem, UpdateFailure,
+};
+use valence_coprocessor_sp1::Sp1Hasher;
+
+fn fixture(name: &str) -> CircuitInput {
+    let path = format!("{}/../../assets/{name}", env!("CARGO_MANIFEST_DIR"));
+    let input = std::fs::read(&path).unwrap_or_else(|_| panic!("missing `{path}` fixture"));
+
+    serde_json::from_slice(&input).unwrap()
+}
+
+fn input() -> CircuitInput {
+    fixture("input.json")
+}
+
+/// Verifies the blocks of the elected domains, falling back to the default domains if the input
+/// doesn't elect any. Returns the number of verified blocks of `system`.
+fn verify_blocks(input: CircuitInput, system: ProofSystem) -> usize {
+    let domains = match input.domains.is_empty() {
+        true => Circuit::default().domains,
+        false => input.domains,
+    };
+    let mut verified = 0;
+
+    for u in input.updates {
+        let block = &u.update.block;
+
+        if let Some(d) = domains.iter().find(|d| d.id == block.domain) {
+            d.verify::<Sp1Hasher, NoRecursion>(block.number, block.root, &block.payload)
+                .unwrap();
+            verified += (d.system == system) as usize;
+        }
+    }
+
+    verified
+}
+
+#[test]
+#[ignore = "requires the `assets/input.json` fixture"]
+fn circuit_root_works() {
+    let CircuitInput { updates, .. } = input();
+    let circuit = Circuit::default();
+
+    circuit.root::<Sp1Hasher, NoRecursion>(updates).unwrap();
+}
+
+#[test]
+#[ignore = "requires the `assets/input.json` fixture"]
+fn circuit_root_reports_failing_update() {
+    let CircuitInput { mut updates, .. } = input();
+    let circuit = Circuit::default();
+
+    // replaying the first update breaks the root chain
+    let index = updates.len();
+    let domain = updates[0].update.block.domain;
+
+    updates.push(updates[0].clone());
+
+    let error = circuit.root::<Sp1Hasher, NoRecursion>(updates).unwrap_err();
+
+    assert_eq!(
+        error.downcast_ref::<UpdateFailure>(),
+        Some(&UpdateFailure { index, domain })
+    );
+    assert!(format!("{error:#}").ends_with(": unexpected root"));
+}
+
+#[test]
+#[ignore = "requires the `assets/input.json` fixture"]
+fn domain_verify_accepts_groth16_blocks() {
+    assert!(verify_blocks(input(), ProofSystem::Groth16) > 0);
+}
+
+#[test]
+#[ignore = "requires the `assets/plonk.json` fixture"]
+fn domain_verify_accepts_plonk_blocks() {
+    assert!(verify_blocks(fixture("plonk.json"), ProofSystem::Plonk) > 0);
+}
+
+#[test]
+#[ignore = "requires the `assets/plonk.json` fixture"]
+fn circuit_root_accepts_plonk_domains() {
+    let CircuitInput {
+        updates, domains, ..
+    } = fixture("plonk.json");
+    let circuit = Circuit {
+        domains,
+        ..Circuit::default()
+    };
+
+    assert!(circuit
+        .domains
+        .iter()
+        .any(|d| d.system == ProofSystem::Plonk));
+
+    let summary = circuit.root::<Sp1Hasher, NoRecursion>(updates).unwrap();
+
+    assert!(!summary.blocks.is_empty());
+}
@@ -0,0 +1,73 @@
+use valence_coprocessor_domain_prover::State;
+
+use crate::{App, Publication};
+
+impl App {
+    /// Returns the cached states, oldest first.
+    pub async fn states(&self) -> Vec<State> {
+        self.service.lock().await.iter().cloned().collect()
+    }
+
+    /// Returns the cache capacity.
+    pub async fn capacity(&self) -> usize {
+        self.service.lock().await.capacity()
+    }
+
+    /// Evicts a cached state, returning it if present.
+    pub async fn evict(&self, uuid: &[u8; 16]) -> anyhow::Result<Option<State>> {
+        let state = {
+            let mut service = self.service.lock().await;
+            let state = service.remove(uuid);
+
+            self.observe_service(&service);
+            state
+        };
+
+        if state.is_some() {
+            tracing::info!("evicted state `{}`...", hex::encode(uuid));
+
+            self.flush_states().await?;
+        }
+
+        Ok(state)
+    }
+
+    /// Enqueues a proving job for the current co-processor head, returning the job id.
+    pub async fn prove_head(&self) -> anyhow::Result<u64> {
+        self.prove(self.coprocessor.get_historical().await?).await
+    }
+
+    /// Publishes a cached state to the co-processor, even if a newer state was published.
+    ///
+    /// Returns `None` if the state is not cached.
+    pub async fn republish(&self, uuid: &[u8; 16]) -> anyhow::Result<Option<Publication>> {
+        let state = match self.state_by_uuid(uuid).await {
+            Some(s) => s,
+            None => return Ok(None),
+        };
+
+        tracing::info!("republishing state `{}`...", hex::encode(uuid));
+
+        self.publish_state(&state, true).await.map(Some)
+    }
+
+    /// Updates the cache capacity, returning the number of evicted states.
+    pub async fn set_capacity(&self, capacity: usize) -> anyhow::Result<usize> {
+        let evicted = {
+            let mut service = self.service.lock().await;
+            let evicted = service.set_capacity(capacity).len();
+
+            tracing::info!(
+                "capacity set to `{}`; evicted `{evicted}` states...",
+                service.capacity()
+            );
+
+            self.observe_service(&service);
+            evicted
+        };
+
+        self.flush_states().await?;
+
+        Ok(evicted)
+    }
+}
@@ -0,0 +1,162 @@
+use anyhow::Context as _;
+use async_trait::async_trait;
+use sp1_sdk::SP1VerifyingKey;
+use valence_coprocessor::{Hash, HistoricalTransitionProof, HistoricalUpdate, Proof};
+use valence_coprocessor_client::Client as CoprocessorClient;
+use valence_coprocessor_domain_prover::RecursiveVerifier;
+pub use valence_coprocessor_domain_prover_verifier::Sp1Verifier;
+use valence_coprocessor_prover::{
+    client::Client as ProverClient,
+    types::{ProofType, RecursiveProof},
+};
+
+use crate::{FailureClass, INNER_VK};
+
+/// The co-processor operations used by the service.
+#[async_trait]
+pub trait Coprocessor: Send + Sync {
+    /// Returns the raw storage of the controller.
+    async fn get_storage_raw(&self, controller: &str) -> anyhow::Result<Vec<u8>>;
+
+    /// Overwrites the raw storage of the controller.
+    async fn set_storage_raw(&self, controller: &str, data: Vec<u8>) -> anyhow::Result<bool>;
+
+    /// Returns the latest historical root.
+    async fn get_historical(&self) -> anyhow::Result<Hash>;
+
+    /// Returns the historical update that produced `root`.
+    async fn get_historical_update(&self, root: &Hash) -> anyhow::Result<HistoricalUpdate>;
+
+    /// Returns the transition proofs from `from` (exclusive) to `to` (inclusive).
+    async fn get_historical_updates(
+        &self,
+        from: &Hash,
+        to: &Hash,
+    ) -> anyhow::Result<Vec<HistoricalTransitionProof>>;
+}
+
+/// A compressed proof provided to the zkVM as a deferred proof.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeferredProof {
+    /// The compressed proof.
+    pub proof: Proof,
+
+    /// The CBOR encoded SP1 verifying key of the proven program.
+    pub vk: Vec<u8>,
+}
+
+impl DeferredProof {
+    /// A compressed proof of the inner circuit.
+    pub fn inner(proof: Proof) -> Self {
+        Self {
+            proof,
+            vk: INNER_VK.to_vec(),
+        }
+    }
+}
+
+/// The prover operations used by the service.
+pub trait Prover: Send + Sync {
+    /// Computes a proof of `elf` for the given input.
+    ///
+    /// The `recursive` proofs are provided to the zkVM as deferred proofs, in the order the
+    /// program verifies them.
+    fn get_sp1_proof(
+        &self,
+        circuit: Hash,
+        proof_type: ProofType,
+        input: &[u8],
+        recursive: &[DeferredProof],
+        elf: &'static [u8],
+    ) -> anyhow::Result<Proof>;
+}
+
+/// Verifies the proofs returned by the prover before they are inserted.
+pub trait Verifier: Send + Sync {
+    /// Verifies a compressed proof of the inner circuit against [INNER_VK].
+    fn verify_inner(&self, proof: &Proof) -> anyhow::Result<()>;
+
+    /// Verifies a Groth16 proof of the wrapper circuit against [WRAPPER_VK].
+    fn verify_wrapper(&self, proof: &Proof) -> anyhow::Result<()>;
+}
+
+impl Verifier for Sp1Verifier {
+    fn verify_inner(&self, proof: &Proof) -> anyhow::Result<()> {
+        Sp1Verifier::verify_inner(self, proof)
+    }
+
+    fn verify_wrapper(&self, proof: &Proof) -> anyhow::Result<()> {
+        Sp1Verifier::verify_wrapper(self, proof)
+    }
+}
+
+#[async_trait]
+impl Coprocessor for CoprocessorClient {
+    async fn get_storage_raw(&self, controller: &str) -> anyhow::Result<Vec<u8>> {
+        CoprocessorClient::get_storage_raw(self, controller)
+            .await
+            .context(FailureClass::Network)
+    }
+
+    async fn set_storage_raw(&self, controller: &str, data: Vec<u8>) -> anyhow::Result<bool> {
+        CoprocessorClient::set_storage_raw(self, controller, data)
+            .await
+            .context(FailureClass::Network)
+    }
+
+    async fn get_historical(&self) -> anyhow::Result<Hash> {
+        CoprocessorClient::get_historical(self)
+            .await
+            .context(FailureClass::Network)
+    }
+
+    async fn get_historical_update(&self, root: &Hash) -> anyhow::Result<HistoricalUpdate> {
+        CoprocessorClient::get_historical_update(self, root)
+            .await
+            .context(FailureClass::Network)
+    }
+
+    async fn get_historical_updates(
+        &self,
+        from: &Hash,
+        to: &Hash,
+    ) -> anyhow::Result<Vec<HistoricalTransitionProof>> {
+        CoprocessorClient::get_historical_updates(self, from, to)
+            .await
+            .context(FailureClass::Network)
+    }
+}
+
+impl Prover for ProverClient {
+    fn get_sp1_proof(
+        &self,
+        circuit: Hash,
+        proof_type: ProofType,
+        input: &[u8],
+        recursive: &[DeferredProof],
+        elf: &'static [u8],
+    ) -> anyhow::Result<Proof> {
+        let recursive = recursive
+            .iter()
+            .map(|p| {
+                let vk: SP1VerifyingKey = serde_cbor::from_slice(&p.vk)?;
+
+                RecursiveProof::try_from_compressed_proof(&p.proof, vk.vk)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        ProverClient::get_sp1_proof(self, circuit, proof_type, input, &recursive, |_| {
+            Ok(elf.to_vec())
+        })
+        .context(FailureClass::Prover)
+    }
+}
+
+/// Accepts the recursive light-client proofs, verified by the prover as deferred proofs.
+pub(crate) struct DeferredRecursion;
+
+impl RecursiveVerifier for DeferredRecursion {
+    fn verify(_vk: &[u32; 8], _digest: &Hash) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
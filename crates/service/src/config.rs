@@ -0,0 +1,275 @@
+use std::{env, fs, net::SocketAddr, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+use valence_coprocessor_domain_prover::EvictionPolicy;
+
+use crate::{FAILURE_THRESHOLD, HISTORY_CAPACITY, WEBHOOK_FAILURE_THRESHOLD};
+
+/// Prefix of the environment variables overriding the configuration keys.
+///
+/// `DOMAIN_PROVER_MAX_WRAPPERS=2` overrides `max_wrappers`. Values are parsed as TOML, falling
+/// back to plain strings; the string keys are always taken verbatim. Variables of unknown keys
+/// are ignored.
+pub const ENV_PREFIX: &str = "DOMAIN_PROVER_";
+
+/// Keys whose environment values are taken verbatim instead of parsed as TOML.
+const STRING_KEYS: &[&str] = &[
+    "bind",
+    "coprocessor",
+    "prover",
+    "domains",
+    "webhook_secret",
+    "webhook_dead_letter",
+    "storage",
+    "admin_token",
+];
+
+/// The configuration of the service binary.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Socket the API binds to.
+    pub bind: SocketAddr,
+
+    /// Address to the co-processor service backend.
+    pub coprocessor: String,
+
+    /// Address to the prover service backend.
+    pub prover: String,
+
+    /// Cache capacity.
+    pub capacity: usize,
+
+    /// Retention policy of the cache at capacity: `"latest"`, `"logarithmic"`, or
+    /// `{ ttl = <seconds> }`.
+    pub eviction: EvictionPolicy,
+
+    /// Update interval (ms).
+    pub interval: u64,
+
+    /// Path to a JSON file with the elected domains. Defaults to the build-time set.
+    pub domains: Option<PathBuf>,
+
+    /// Rejects updates of domains that are not elected.
+    pub strict: bool,
+
+    /// Maximum number of wrapper proofs computed concurrently with the next inner proof.
+    pub max_wrappers: usize,
+
+    /// Maximum number of historical updates per inner proof. Unbounded if omitted.
+    pub max_updates: Option<usize>,
+
+    /// Webhook targets notified of published states and failures.
+    pub webhooks: Vec<String>,
+
+    /// HMAC secret used to sign the webhook bodies.
+    pub webhook_secret: Option<String>,
+
+    /// Path to a JSON lines file of the undelivered webhook notifications.
+    pub webhook_dead_letter: Option<PathBuf>,
+
+    /// Consecutive update failures between two `update_failed` webhook notifications.
+    pub webhook_failure_threshold: u64,
+
+    /// Number of update intervals without a successful update before the service is not ready.
+    pub ready_intervals: u32,
+
+    /// Maximum time to wait for the in-flight proofs on shutdown (s).
+    pub shutdown_timeout: u64,
+
+    /// Consecutive update failures before the service is reported as degraded.
+    pub failure_threshold: u64,
+
+    /// Path to a file-backed storage for the computed states. Memory-only if omitted.
+    pub storage: Option<PathBuf>,
+
+    /// Maximum number of historical updates and transition proofs cached. Disabled if zero.
+    pub history_capacity: usize,
+
+    /// Persists the historical fetch cache alongside the states.
+    pub persist_history: bool,
+
+    /// Bearer token of the admin API. The admin API is disabled if omitted.
+    pub admin_token: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bind: SocketAddr::from(([0, 0, 0, 0], 37279)),
+            coprocessor: "https://service.coprocessor.valence.zone".into(),
+            prover: "wss://prover.coprocessor.valence.zone".into(),
+            capacity: 1000,
+            eviction: EvictionPolicy::Latest,
+            interval: 60000,
+            domains: None,
+            strict: false,
+            max_wrappers: 1,
+            max_updates: None,
+            webhooks: Vec::new(),
+            webhook_secret: None,
+            webhook_dead_letter: None,
+            webhook_failure_threshold: WEBHOOK_FAILURE_THRESHOLD,
+            ready_intervals: 3,
+            shutdown_timeout: 600,
+            failure_threshold: FAILURE_THRESHOLD,
+            storage: None,
+            history_capacity: HISTORY_CAPACITY,
+            persist_history: false,
+            admin_token: None,
+        }
+    }
+}
+
+impl Config {
+    /// The configuration keys.
+    pub const KEYS: &'static [&'static str] = &[
+        "bind",
+        "coprocessor",
+        "prover",
+        "capacity",
+        "eviction",
+        "interval",
+        "domains",
+        "strict",
+        "max_wrappers",
+        "max_updates",
+        "webhooks",
+        "webhook_secret",
+        "webhook_dead_letter",
+        "webhook_failure_threshold",
+        "ready_intervals",
+        "shutdown_timeout",
+        "failure_threshold",
+        "storage",
+        "history_capacity",
+        "persist_history",
+        "admin_token",
+    ];
+
+    /// Parses a TOML configuration, applying the overrides of the `ENV_PREFIX` variables.
+    ///
+    /// Missing keys take their default value.
+    pub fn from_toml<I>(toml: &str, vars: I) -> anyhow::Result<Self>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let mut table: toml::Table = toml
+            .parse()
+            .map_err(|e| anyhow::anyhow!("invalid configuration: {e}"))?;
+
+        for (var, value) in vars {
+            let key = match var.strip_prefix(ENV_PREFIX) {
+                Some(k) => k.to_lowercase(),
+                None => continue,
+            };
+
+            if !Self::KEYS.contains(&key.as_str()) {
+                tracing::warn!("ignoring `{var}`; `{key}` is not a configuration key...");
+                continue;
+            }
+
+            let value = match STRING_KEYS.contains(&key.as_str()) {
+                true => toml::Value::String(value),
+                false => Self::env_value(value),
+            };
+
+            table.insert(key, value);
+        }
+
+        toml::Value::Table(table)
+            .try_into()
+            .map_err(|e| anyhow::anyhow!("invalid configuration: {e}"))
+    }
+
+    /// Loads the configuration file, if any, applying the environment overrides.
+    pub fn load(path: Option<&PathBuf>) -> anyhow::Result<Self> {
+        let toml = match path {
+            Some(p) => fs::read_to_string(p)
+                .map_err(|e| anyhow::anyhow!("failed to read `{}`: {e}", p.display()))?,
+            None => String::new(),
+        };
+
+        Self::from_toml(&toml, env::vars())
+    }
+
+    fn env_value(value: String) -> toml::Value {
+        format!("value = {value}")
+            .parse::<toml::Table>()
+            .ok()
+            .and_then(|mut t| t.remove("value"))
+            .unwrap_or(toml::Value::String(value))
+    }
+
+    /// Checks the configuration values, describing the first invalid key.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        anyhow::ensure!(self.capacity > 0, "`capacity` must be positive");
+        anyhow::ensure!(self.interval > 0, "`interval` must be positive");
+        anyhow::ensure!(self.max_wrappers > 0, "`max_wrappers` must be positive");
+        anyhow::ensure!(
+            self.max_updates != Some(0),
+            "`max_updates` must be positive"
+        );
+        anyhow::ensure!(
+            self.ready_intervals > 0,
+            "`ready_intervals` must be positive"
+        );
+        anyhow::ensure!(
+            self.failure_threshold > 0,
+            "`failure_threshold` must be positive"
+        );
+        anyhow::ensure!(
+            self.webhook_failure_threshold > 0,
+            "`webhook_failure_threshold` must be positive"
+        );
+        anyhow::ensure!(!self.coprocessor.is_empty(), "`coprocessor` is empty");
+        anyhow::ensure!(!self.prover.is_empty(), "`prover` is empty");
+
+        for url in &self.webhooks {
+            anyhow::ensure!(
+                url.starts_with("http://") || url.starts_with("https://"),
+                "`webhooks` entry `{url}` is not an http(s) URL"
+            );
+        }
+
+        anyhow::ensure!(
+            self.webhook_secret.is_none() || !self.webhooks.is_empty(),
+            "`webhook_secret` is set without `webhooks`"
+        );
+
+        anyhow::ensure!(
+            !self.persist_history || self.storage.is_some(),
+            "`persist_history` is set without `storage`"
+        );
+
+        anyhow::ensure!(
+            !matches!(self.admin_token.as_deref(), Some("")),
+            "`admin_token` is empty"
+        );
+
+        if let Some(domains) = &self.domains {
+            anyhow::ensure!(
+                domains.is_file(),
+                "`domains` file `{}` not found",
+                domains.display()
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Returns the configuration as TOML, with the secrets redacted.
+    pub fn to_toml(&self) -> anyhow::Result<String> {
+        let mut config = self.clone();
+
+        if config.webhook_secret.is_some() {
+            config.webhook_secret = Some("<redacted>".into());
+        }
+
+        if config.admin_token.is_some() {
+            config.admin_token = Some("<redacted>".into());
+        }
+
+        Ok(toml::to_string_pretty(&config)?)
+    }
+}
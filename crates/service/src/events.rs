@@ -0,0 +1,18 @@
+use serde::Serialize;
+use valence_coprocessor_domain_prover::State;
+
+/// Maximum number of events buffered per subscriber; slower subscribers skip the oldest ones.
+pub const EVENTS_CAPACITY: usize = 64;
+
+/// A state accepted by the service.
+#[derive(Debug, Clone, Serialize)]
+pub struct StateEvent {
+    /// The inserted state.
+    pub state: State,
+
+    /// Whether the state became the latest.
+    pub latest: bool,
+
+    /// Whether the co-processor storage was updated with the state.
+    pub published: bool,
+}
@@ -0,0 +1,243 @@
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde::Serialize;
+
+/// Default number of consecutive failures before the service is degraded.
+pub const FAILURE_THRESHOLD: u64 = 5;
+
+/// The class of an update failure, attached as context to the errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureClass {
+    /// The co-processor could not be reached.
+    Network,
+
+    /// The prover failed to compute a proof.
+    Prover,
+
+    /// The prover failed to compute a wrapper proof, after its inner proof.
+    Wrapper,
+
+    /// The historical updates or the computed proofs are invalid.
+    InvalidUpdate,
+
+    /// No cached state precedes the target root.
+    MissingLowerBound,
+
+    /// The failure was not classified.
+    Unknown,
+}
+
+impl fmt::Display for FailureClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Network => "network failure",
+            Self::Prover => "prover failure",
+            Self::Wrapper => "wrapper failure",
+            Self::InvalidUpdate => "invalid update",
+            Self::MissingLowerBound => "missing lower bound",
+            Self::Unknown => "unknown failure",
+        };
+
+        f.write_str(s)
+    }
+}
+
+impl FailureClass {
+    /// Returns the snake case name of the class.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Network => "network",
+            Self::Prover => "prover",
+            Self::Wrapper => "wrapper",
+            Self::InvalidUpdate => "invalid_update",
+            Self::MissingLowerBound => "missing_lower_bound",
+            Self::Unknown => "unknown",
+        }
+    }
+
+    /// Returns the class attached to the error, if any.
+    pub fn of(error: &anyhow::Error) -> Self {
+        error.downcast_ref().copied().unwrap_or(Self::Unknown)
+    }
+
+    /// Returns the delay before retrying after `failures` consecutive failures.
+    ///
+    /// Starts at a per-class base and doubles on each failure, up to a per-class cap.
+    pub fn backoff(&self, failures: u64) -> Duration {
+        let (base, max) = match self {
+            Self::Network => (5, 300),
+            Self::Prover => (30, 1800),
+            Self::Wrapper => (30, 1800),
+            Self::InvalidUpdate => (300, 3600),
+            Self::MissingLowerBound => (60, 3600),
+            Self::Unknown => (10, 600),
+        };
+
+        let exp = failures.saturating_sub(1).min(16) as u32;
+
+        Duration::from_secs(base * 2u64.pow(exp)).min(Duration::from_secs(max))
+    }
+}
+
+/// The health of the background updates.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    #[default]
+    Healthy,
+
+    /// The consecutive failures reached the threshold.
+    Degraded,
+}
+
+/// A snapshot of the health of the background updates.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct HealthReport {
+    pub status: HealthStatus,
+
+    /// Consecutive failed updates.
+    pub failures: u64,
+
+    /// Class of the last failure, reset on success.
+    pub class: Option<FailureClass>,
+
+    /// Message of the last failure, reset on success.
+    pub error: Option<String>,
+
+    /// Delay before the next attempt, in seconds.
+    pub retry_in: u64,
+
+    /// Unix timestamp, in seconds, of the last successful update.
+    pub last_success: u64,
+}
+
+/// The readiness of the service to serve fresh proofs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Readiness {
+    /// Whether all the checks passed.
+    pub ready: bool,
+
+    /// A latest state is loaded.
+    pub loaded: bool,
+
+    /// The co-processor answered with its head.
+    pub coprocessor: bool,
+
+    /// The last successful update is within the staleness limit.
+    pub fresh: bool,
+
+    /// UUID of the latest proven update.
+    pub latest: Option<String>,
+
+    /// UUID of the co-processor head.
+    pub head: Option<String>,
+
+    /// Distance between the latest proven update and the co-processor head, as measured by the
+    /// last update.
+    pub lag: Lag,
+
+    /// The health of the background updates.
+    pub health: HealthReport,
+}
+
+/// Distance between the latest proven update and the co-processor head.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Lag {
+    /// Historical updates not yet proven.
+    pub updates: u64,
+
+    /// Seconds since the head was first seen ahead of the latest proven update.
+    pub seconds: u64,
+}
+
+/// Tracks the consecutive failures of the background updates.
+#[derive(Debug, Clone)]
+pub struct Health {
+    report: Arc<Mutex<HealthReport>>,
+    threshold: u64,
+    behind_since: Arc<Mutex<Option<u64>>>,
+    lag: Arc<Mutex<Lag>>,
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self::new(FAILURE_THRESHOLD)
+    }
+}
+
+impl Health {
+    pub fn new(threshold: u64) -> Self {
+        Self {
+            report: Default::default(),
+            threshold: threshold.max(1),
+            behind_since: Default::default(),
+            lag: Default::default(),
+        }
+    }
+
+    /// Records whether the latest proven update is behind the co-processor head, returning
+    /// for how many seconds it has been.
+    pub fn behind(&self, behind: bool) -> u64 {
+        let mut since = self.behind_since.lock().unwrap_or_else(|e| e.into_inner());
+        let now = crate::timestamp();
+
+        if !behind {
+            since.take();
+            return 0;
+        }
+
+        now.saturating_sub(*since.get_or_insert(now))
+    }
+
+    /// Records the lag measured by the last update.
+    pub fn set_lag(&self, lag: Lag) {
+        *self.lag.lock().unwrap_or_else(|e| e.into_inner()) = lag;
+    }
+
+    /// Returns the lag measured by the last update.
+    pub fn lag(&self) -> Lag {
+        *self.lag.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Records a successful update.
+    pub fn succeeded(&self) {
+        *self.report.lock().unwrap_or_else(|e| e.into_inner()) = HealthReport {
+            last_success: crate::timestamp(),
+            ..Default::default()
+        };
+    }
+
+    /// Records a failed update, returning the delay before the next attempt.
+    ///
+    /// The delay is never shorter than the regular update `interval`.
+    pub fn failed(&self, error: &anyhow::Error, interval: Duration) -> Duration {
+        let class = FailureClass::of(error);
+        let mut report = self.report.lock().unwrap_or_else(|e| e.into_inner());
+
+        report.failures += 1;
+
+        let backoff = class.backoff(report.failures).max(interval);
+
+        report.class = Some(class);
+        report.error = Some(format!("{error:#}"));
+        report.retry_in = backoff.as_secs();
+
+        if report.failures >= self.threshold {
+            report.status = HealthStatus::Degraded;
+        }
+
+        backoff
+    }
+
+    pub fn report(&self) -> HealthReport {
+        self.report
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
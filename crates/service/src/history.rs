@@ -0,0 +1,304 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+};
+
+use msgpacker::MsgPacker;
+use prometheus::IntCounterVec;
+use serde::Serialize;
+use valence_coprocessor::{Hash, HistoricalTransitionProof, HistoricalUpdate};
+
+use crate::App;
+
+/// Default number of historical entries kept by the fetch cache.
+pub const HISTORY_CAPACITY: usize = 4096;
+
+/// A historical entry fetched from the co-processor.
+#[derive(Debug, Clone, PartialEq, Eq, MsgPacker)]
+pub enum HistoryEntry {
+    /// The update that produced a root.
+    Update(HistoricalUpdate),
+
+    /// The transition proof of an update.
+    Transition(HistoricalTransitionProof),
+}
+
+impl HistoryEntry {
+    pub fn update(&self) -> &HistoricalUpdate {
+        match self {
+            Self::Update(u) => u,
+            Self::Transition(t) => &t.update,
+        }
+    }
+}
+
+/// Lookup statistics of the historical fetch cache.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize)]
+pub struct HistoryStats {
+    /// Cached entries.
+    pub entries: usize,
+
+    /// Maximum number of cached entries.
+    pub capacity: usize,
+
+    /// Lookups answered from the cache.
+    pub hits: u64,
+
+    /// Lookups forwarded to the co-processor.
+    pub misses: u64,
+
+    /// Ratio of the lookups answered from the cache.
+    pub hit_rate: f64,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    items: HashMap<Hash, (u64, HistoryEntry)>,
+    order: BTreeMap<u64, Hash>,
+    tick: u64,
+}
+
+impl Entries {
+    fn get(&mut self, root: &Hash) -> Option<&HistoryEntry> {
+        self.touch(root);
+        self.peek(root)
+    }
+
+    /// Returns the entry without updating its recency.
+    fn peek(&self, root: &Hash) -> Option<&HistoryEntry> {
+        self.items.get(root).map(|(_, e)| e)
+    }
+
+    /// Marks the entry as the most recently used.
+    fn touch(&mut self, root: &Hash) {
+        let tick = match self.items.get_mut(root) {
+            Some((tick, _)) => tick,
+            None => return,
+        };
+
+        self.order.remove(tick);
+        self.tick += 1;
+        self.order.insert(self.tick, *root);
+
+        *tick = self.tick;
+    }
+
+    fn insert(&mut self, entry: HistoryEntry, capacity: usize) {
+        let root = entry.update().root;
+
+        // a transition proof also answers the update lookups
+        let entry = match (self.get(&root), entry) {
+            (Some(HistoryEntry::Transition(_)), HistoryEntry::Update(_)) => return,
+            (_, entry) => entry,
+        };
+
+        self.tick += 1;
+
+        if let Some((tick, _)) = self.items.insert(root, (self.tick, entry)) {
+            self.order.remove(&tick);
+        }
+
+        self.order.insert(self.tick, root);
+
+        while self.items.len() > capacity {
+            match self.order.pop_first() {
+                Some((_, root)) => self.items.remove(&root),
+                None => break,
+            };
+        }
+    }
+}
+
+/// A least-recently-used cache of the historical updates and transition proofs fetched from
+/// the co-processor, keyed by root.
+#[derive(Debug, Clone)]
+pub struct HistoryCache {
+    entries: Arc<Mutex<Entries>>,
+    capacity: usize,
+    persistent: bool,
+    lookups: IntCounterVec,
+}
+
+impl Default for HistoryCache {
+    fn default() -> Self {
+        Self::new(HISTORY_CAPACITY)
+    }
+}
+
+impl HistoryCache {
+    /// Creates a cache of up to `capacity` entries. A zero capacity disables the cache.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Default::default(),
+            capacity,
+            persistent: false,
+            lookups: crate::metrics::history_lookups().unwrap(),
+        }
+    }
+
+    /// Records the lookups on the provided counter, by `result` (`hit` or `miss`).
+    pub fn with_lookups(mut self, lookups: IntCounterVec) -> Self {
+        self.lookups = lookups;
+        self
+    }
+
+    /// Persists the entries alongside the states, reloading them on init.
+    pub fn with_persistence(mut self, persistent: bool) -> Self {
+        self.persistent = persistent;
+        self
+    }
+
+    pub fn is_persistent(&self) -> bool {
+        self.persistent
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Entries> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn record(&self, hit: bool) {
+        let result = if hit { "hit" } else { "miss" };
+
+        self.lookups.with_label_values(&[result]).inc();
+    }
+
+    /// Returns the cached update that produced `root`.
+    pub fn update(&self, root: &Hash) -> Option<HistoricalUpdate> {
+        if self.capacity == 0 {
+            return None;
+        }
+
+        let update = self.lock().get(root).map(|e| e.update().clone());
+
+        self.record(update.is_some());
+
+        update
+    }
+
+    /// Returns the cached transition proofs from `from` (exclusive) to `to` (inclusive).
+    ///
+    /// Misses unless every transition of the range is cached; only a hit updates the recency of
+    /// the entries.
+    pub fn transitions(&self, from: &Hash, to: &Hash) -> Option<Vec<HistoricalTransitionProof>> {
+        if self.capacity == 0 {
+            return None;
+        }
+
+        let mut entries = self.lock();
+        let mut transitions = Vec::new();
+        let mut root = *to;
+
+        while &root != from && transitions.len() < entries.items.len() {
+            match entries.peek(&root) {
+                Some(HistoryEntry::Transition(t)) => {
+                    root = t.update.previous;
+                    transitions.push(t.clone());
+                }
+                _ => break,
+            }
+        }
+
+        let hit = &root == from;
+
+        if hit {
+            for t in transitions.iter().rev() {
+                entries.touch(&t.update.root);
+            }
+        }
+
+        drop(entries);
+
+        self.record(hit);
+
+        hit.then(|| transitions.into_iter().rev().collect())
+    }
+
+    pub fn insert_update(&self, update: HistoricalUpdate) {
+        self.extend([HistoryEntry::Update(update)]);
+    }
+
+    pub fn insert_transitions(&self, transitions: &[HistoricalTransitionProof]) {
+        self.extend(transitions.iter().cloned().map(HistoryEntry::Transition));
+    }
+
+    /// Inserts the entries, evicting the least recently used in excess.
+    pub fn extend<I: IntoIterator<Item = HistoryEntry>>(&self, entries: I) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut cache = self.lock();
+
+        for entry in entries {
+            cache.insert(entry, self.capacity);
+        }
+    }
+
+    /// Returns the cached entries, least recently used first.
+    pub fn entries(&self) -> Vec<HistoryEntry> {
+        let cache = self.lock();
+
+        cache
+            .order
+            .values()
+            .filter_map(|root| cache.items.get(root))
+            .map(|(_, e)| e.clone())
+            .collect()
+    }
+
+    pub fn stats(&self) -> HistoryStats {
+        let hits = self.lookups.with_label_values(&["hit"]).get();
+        let misses = self.lookups.with_label_values(&["miss"]).get();
+        let lookups = hits + misses;
+
+        HistoryStats {
+            entries: self.lock().items.len(),
+            capacity: self.capacity,
+            hits,
+            misses,
+            hit_rate: if lookups == 0 {
+                0.0
+            } else {
+                hits as f64 / lookups as f64
+            },
+        }
+    }
+}
+
+impl App {
+    /// Returns the lookup statistics of the historical fetch cache.
+    pub fn history(&self) -> HistoryStats {
+        self.history.stats()
+    }
+
+    /// Returns the historical update that produced `root`, fetching it on a cache miss.
+    pub(crate) async fn historical_update(&self, root: &Hash) -> anyhow::Result<HistoricalUpdate> {
+        if let Some(update) = self.history.update(root) {
+            return Ok(update);
+        }
+
+        let update = self.coprocessor.get_historical_update(root).await?;
+
+        self.history.insert_update(update.clone());
+
+        Ok(update)
+    }
+
+    /// Returns the transition proofs from `from` (exclusive) to `to` (inclusive), fetching the
+    /// range on a cache miss.
+    pub(crate) async fn historical_updates(
+        &self,
+        from: &Hash,
+        to: &Hash,
+    ) -> anyhow::Result<Vec<HistoricalTransitionProof>> {
+        if let Some(updates) = self.history.transitions(from, to) {
+            return Ok(updates);
+        }
+
+        let updates = self.coprocessor.get_historical_updates(from, to).await?;
+
+        self.history.insert_transitions(&updates);
+
+        Ok(updates)
+    }
+}
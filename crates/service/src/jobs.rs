@@ -0,0 +1,136 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, Semaphore};
+use valence_coprocessor_domain_prover::UpdateFailure;
+
+/// Maximum number of tracked jobs; finished ones are discarded to make room for new ones.
+pub const JOBS_CAPACITY: usize = 1000;
+
+/// The status of an on-demand proving job.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum JobStatus {
+    /// Waiting for the prover to be available.
+    Queued,
+
+    /// Computing the inner (compressed) proof.
+    ProvingInner,
+
+    /// Computing the wrapper (Groth16) proof.
+    Wrapping,
+
+    /// The state was proven and inserted into the service state.
+    Done { root: String, uuid: String },
+
+    /// The job failed.
+    Failed {
+        reason: String,
+
+        /// The update rejected by the native execution of the circuit, if any.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        update: Option<FailedUpdate>,
+    },
+}
+
+/// A historical update rejected by the circuit.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FailedUpdate {
+    /// Position of the update in the fetched batch.
+    pub index: usize,
+
+    /// Domain of the update block.
+    pub domain: String,
+
+    /// Why the update was rejected.
+    pub reason: String,
+}
+
+impl FailedUpdate {
+    /// Returns the rejected update attached to the error, if any.
+    pub fn of(error: &anyhow::Error) -> Option<Self> {
+        let failure = error.downcast_ref::<UpdateFailure>()?;
+        let context = failure.to_string();
+        let reason: Vec<_> = error
+            .chain()
+            .map(ToString::to_string)
+            .skip_while(|c| c != &context)
+            .skip(1)
+            .collect();
+
+        Some(Self {
+            index: failure.index,
+            domain: hex::encode(failure.domain),
+            reason: reason.join(": "),
+        })
+    }
+}
+
+impl JobStatus {
+    /// Returns `true` if the job will not transition anymore.
+    pub fn is_finished(&self) -> bool {
+        matches!(self, Self::Done { .. } | Self::Failed { .. })
+    }
+}
+
+/// A registry of on-demand proving jobs.
+#[derive(Debug, Clone)]
+pub struct Jobs {
+    items: Arc<Mutex<BTreeMap<u64, JobStatus>>>,
+    next: Arc<Mutex<u64>>,
+    permits: Arc<Semaphore>,
+}
+
+impl Default for Jobs {
+    fn default() -> Self {
+        Self {
+            items: Default::default(),
+            next: Default::default(),
+            permits: Arc::new(Semaphore::new(1)),
+        }
+    }
+}
+
+impl Jobs {
+    /// Registers a new queued job, returning its id.
+    ///
+    /// Returns `None` if [JOBS_CAPACITY] jobs are still running.
+    pub async fn create(&self) -> Option<u64> {
+        let mut items = self.items.lock().await;
+
+        while items.len() >= JOBS_CAPACITY {
+            let finished = items
+                .iter()
+                .find_map(|(id, s)| s.is_finished().then_some(*id))?;
+
+            items.remove(&finished);
+        }
+
+        let id = {
+            let mut next = self.next.lock().await;
+            let id = *next;
+
+            *next += 1;
+            id
+        };
+
+        items.insert(id, JobStatus::Queued);
+
+        Some(id)
+    }
+
+    /// Updates the status of a job.
+    pub async fn set(&self, id: u64, status: JobStatus) {
+        self.items.lock().await.insert(id, status);
+    }
+
+    /// Returns the status of a job.
+    pub async fn get(&self, id: u64) -> Option<JobStatus> {
+        self.items.lock().await.get(&id).cloned()
+    }
+
+    /// Returns the semaphore that serializes job execution.
+    pub fn permits(&self) -> Arc<Semaphore> {
+        self.permits.clone()
+    }
+}
@@ -1,16 +1,53 @@
-use std::sync::Arc;
-
-use msgpacker::Packable as _;
-use sp1_sdk::SP1VerifyingKey;
-use tokio::sync::Mutex;
-use valence_coprocessor::{ControllerData, Hash, Proof};
-use valence_coprocessor_client::Client as Coprocessor;
-use valence_coprocessor_domain_prover::{CircuitInput, ServiceState, State};
-use valence_coprocessor_prover::{
-    client::Client as Prover,
-    types::{ProofType, RecursiveProof},
+use std::{
+    mem,
+    ops::Bound,
+    sync::{atomic::AtomicBool, Arc},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
+use anyhow::Context as _;
+
+use msgpacker::{Packable as _, Unpackable as _};
+use tokio::sync::{broadcast, Mutex};
+use valence_coprocessor::{
+    ControllerData, Hash, HistoricalTransitionProof, HistoricalUpdate, Proof,
+};
+use valence_coprocessor_client::Client as CoprocessorClient;
+use valence_coprocessor_domain_prover::{
+    Circuit, CircuitInput, CircuitOutput, CompressedPayload, Domain, EvictionPolicy, ProofSystem,
+    ServiceState, State, StateMetadata, UpdateFailure, WrapperOutput,
+};
+use valence_coprocessor_prover::{client::Client as ProverClient, types::ProofType};
+use valence_coprocessor_sp1::Sp1Hasher;
+
+mod admin;
+mod backend;
+mod config;
+mod events;
+mod health;
+mod history;
+mod jobs;
+mod metrics;
+mod pipeline;
+mod storage;
+mod summary;
+mod webhooks;
+
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
+
+pub use backend::*;
+pub use config::*;
+pub use events::*;
+pub use health::*;
+pub use history::*;
+pub use jobs::*;
+pub use metrics::*;
+pub use pipeline::*;
+pub use storage::*;
+pub use summary::*;
+pub use webhooks::*;
+
 pub const ID: &[u8] = include_bytes!("../../../elf/id.bin");
 pub const INNER_ELF: &[u8] = include_bytes!("../../../elf/circuit.bin");
 pub const INNER_VK: &[u8] = include_bytes!("../../../elf/circuit-vk.bin");
@@ -18,49 +55,168 @@ pub const INNER_VK_B32: &[u8] = include_bytes!("../../../elf/circuit-vkh32.bin")
 pub const WRAPPER_ELF: &[u8] = include_bytes!("../../../elf/wrapper.bin");
 pub const WRAPPER_VK: &[u8] = include_bytes!("../../../elf/wrapper-bytes32");
 
+/// Maximum number of states returned by a single page.
+pub const MAX_PAGE_SIZE: usize = 1000;
+
 #[derive(Clone)]
 pub struct App {
     service: Arc<Mutex<ServiceState>>,
-    coprocessor: Coprocessor,
-    prover: Prover,
-    inner: SP1VerifyingKey,
+    coprocessor: Arc<dyn Coprocessor>,
+    prover: Arc<dyn Prover>,
+    verifier: Arc<dyn Verifier>,
     inner_hash: Hash,
     wrapper_hash: Hash,
     wrapper_vk: String,
     id: String,
+    jobs: Jobs,
+    storage: Arc<dyn Storage>,
+    writes: Arc<Mutex<()>>,
+    domains: Vec<Domain>,
+    strict: bool,
+    preflight: bool,
+    pipeline: Pipeline,
+    metrics: Metrics,
+    max_updates: usize,
+    events: broadcast::Sender<StateEvent>,
+    webhooks: Webhooks,
+    health: Health,
+    history: HistoryCache,
+    max_staleness: Duration,
+    closing: Arc<AtomicBool>,
 }
 
 impl App {
     pub fn new(capacity: usize) -> Self {
         let service = ServiceState::default().with_capacity(capacity);
         let service = Arc::new(Mutex::new(service));
-        let coprocessor = Coprocessor::default();
-        let prover = Prover::default();
-        let inner = serde_cbor::from_slice(INNER_VK).unwrap();
+        let coprocessor = Arc::new(CoprocessorClient::default());
+        let prover = Arc::new(ProverClient::default());
         let inner_hash = ControllerData::identifier_from_parts(INNER_ELF, 0);
         let wrapper_hash = Hash::try_from(ID).unwrap();
         let wrapper_vk = String::from_utf8(WRAPPER_VK.to_vec()).unwrap();
         let id = State::ID.to_string();
+        let metrics = Metrics::default();
+        let history = HistoryCache::default().with_lookups(metrics.history.clone());
 
         Self {
             service,
             coprocessor,
             prover,
-            inner,
+            verifier: Arc::new(Sp1Verifier::new(INNER_VK, &wrapper_vk).unwrap()),
             inner_hash,
             wrapper_hash,
             wrapper_vk,
             id,
+            jobs: Jobs::default(),
+            storage: Arc::new(MemoryStorage),
+            writes: Default::default(),
+            domains: Circuit::default().domains,
+            strict: false,
+            preflight: true,
+            pipeline: Pipeline::default(),
+            metrics,
+            max_updates: usize::MAX,
+            events: broadcast::channel(EVENTS_CAPACITY).0,
+            webhooks: Webhooks::default(),
+            health: Health::default(),
+            history,
+            max_staleness: Duration::from_secs(180),
+            closing: Default::default(),
         }
     }
 
     pub fn with_coprocessor<C: AsRef<str>>(mut self, coprocessor: C) -> Self {
-        self.coprocessor = self.coprocessor.with_coprocessor(coprocessor);
+        self.coprocessor = Arc::new(CoprocessorClient::default().with_coprocessor(coprocessor));
         self
     }
 
     pub fn with_prover<P: ToString>(mut self, prover: P) -> Self {
-        self.prover = Prover::new(prover);
+        self.prover = Arc::new(ProverClient::new(prover));
+        self
+    }
+
+    pub fn with_coprocessor_backend<C: Coprocessor + 'static>(mut self, coprocessor: C) -> Self {
+        self.coprocessor = Arc::new(coprocessor);
+        self
+    }
+
+    pub fn with_prover_backend<P: Prover + 'static>(mut self, prover: P) -> Self {
+        self.prover = Arc::new(prover);
+        self
+    }
+
+    pub fn with_verifier_backend<V: Verifier + 'static>(mut self, verifier: V) -> Self {
+        self.verifier = Arc::new(verifier);
+        self
+    }
+
+    pub fn with_storage<S: Storage + 'static>(mut self, storage: S) -> Self {
+        self.storage = Arc::new(storage);
+        self
+    }
+
+    pub fn with_domains(mut self, domains: Vec<Domain>) -> Self {
+        self.domains = domains;
+        self
+    }
+
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Executes the inner circuit natively before calling the prover. Enabled by default.
+    ///
+    /// Only backends without verifiable transitions, such as the mock co-processor, disable it.
+    pub fn with_preflight(mut self, preflight: bool) -> Self {
+        self.preflight = preflight;
+        self
+    }
+
+    pub fn with_max_wrappers(mut self, max_wrappers: usize) -> Self {
+        self.pipeline = Pipeline::new(max_wrappers);
+        self
+    }
+
+    /// Caps the number of historical updates verified by a single inner proof.
+    ///
+    /// Longer ranges are proven in sequential recursive chunks.
+    pub fn with_max_updates(mut self, max_updates: usize) -> Self {
+        self.max_updates = max_updates.max(1);
+        self
+    }
+
+    /// Sets the retention policy of the cached states, shared with the clones of the app.
+    ///
+    /// Panics if the cached states are locked, as by a running clone of the app.
+    pub fn with_eviction(self, policy: EvictionPolicy) -> Self {
+        self.service
+            .try_lock()
+            .expect("the cached states are locked")
+            .set_policy(policy);
+        self
+    }
+
+    pub fn with_webhooks(mut self, webhooks: Webhooks) -> Self {
+        self.webhooks = webhooks;
+        self
+    }
+
+    /// Caches the historical updates and transition proofs fetched from the co-processor.
+    pub fn with_history(mut self, history: HistoryCache) -> Self {
+        self.history = history.with_lookups(self.metrics.history.clone());
+        self
+    }
+
+    /// Marks the service as degraded after `threshold` consecutive update failures.
+    pub fn with_failure_threshold(mut self, threshold: u64) -> Self {
+        self.health = Health::new(threshold);
+        self
+    }
+
+    /// Reports the service as not ready if no update succeeded within `max_staleness`.
+    pub fn with_max_staleness(mut self, max_staleness: Duration) -> Self {
+        self.max_staleness = max_staleness;
         self
     }
 
@@ -72,9 +228,186 @@ impl App {
         &self.wrapper_vk
     }
 
+    pub fn domains(&self) -> &[Domain] {
+        &self.domains
+    }
+
+    pub fn strict(&self) -> bool {
+        self.strict
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    pub fn webhooks(&self) -> &Webhooks {
+        &self.webhooks
+    }
+
+    pub fn health(&self) -> HealthReport {
+        self.health.report()
+    }
+
+    /// Checks whether the service is ready to serve fresh proofs.
+    ///
+    /// Queries the co-processor head, waiting at most `timeout`. The lag is the one measured by
+    /// the last update, so the probes never fetch a historical range.
+    pub async fn readiness(&self, timeout: Duration) -> Readiness {
+        let latest = self.latest().await;
+        let head = tokio::time::timeout(timeout, async {
+            let root = self.coprocessor.get_historical().await?;
+
+            self.historical_update(&root).await
+        })
+        .await;
+
+        let head = match head {
+            Ok(Ok(h)) => Some(h),
+            Ok(Err(e)) => {
+                tracing::warn!("co-processor not reachable: {e:#}");
+                None
+            }
+            Err(_) => {
+                tracing::warn!("co-processor not reachable: timeout");
+                None
+            }
+        };
+
+        let health = self.health.report();
+        let age = timestamp().saturating_sub(health.last_success);
+        let fresh = age <= self.max_staleness.as_secs();
+
+        Readiness {
+            ready: latest.is_some() && head.is_some() && fresh,
+            loaded: latest.is_some(),
+            coprocessor: head.is_some(),
+            fresh,
+            latest: latest.map(|l| hex::encode(l.update.uuid)),
+            head: head.map(|h| hex::encode(h.uuid)),
+            lag: self.health.lag(),
+            health,
+        }
+    }
+
+    /// Measures and records the distance between the latest proven state and the head.
+    pub(crate) async fn lag(&self, latest: &State, head: &HistoricalUpdate) -> anyhow::Result<Lag> {
+        let lag = if head.uuid <= latest.update.uuid {
+            Lag {
+                updates: 0,
+                seconds: self.health.behind(false),
+            }
+        } else {
+            let updates = self
+                .historical_updates(&latest.update.root, &head.root)
+                .await?;
+
+            Lag {
+                updates: u64::try_from(updates.len()).unwrap_or(u64::MAX),
+                seconds: self.health.behind(true),
+            }
+        };
+
+        self.metrics.set_lag(&lag);
+        self.health.set_lag(lag);
+
+        Ok(lag)
+    }
+
+    /// Records the outcome of a background update, returning the delay before the next one.
+    pub fn record_update<T>(&self, result: &anyhow::Result<T>, interval: Duration) -> Duration {
+        let e = match result {
+            Ok(_) => {
+                self.health.succeeded();
+
+                return interval;
+            }
+            Err(e) => e,
+        };
+
+        let class = FailureClass::of(e);
+        let backoff = self.health.failed(e, interval);
+
+        tracing::error!("error updating state: {e:#}; retrying in {backoff:?}...");
+
+        self.metrics
+            .failures
+            .with_label_values(&[class.as_str()])
+            .inc();
+        self.webhooks
+            .update_failed(self.health.report().failures, e);
+
+        backoff
+    }
+
+    /// Subscribes to the states accepted by the service.
+    pub fn subscribe(&self) -> broadcast::Receiver<StateEvent> {
+        self.events.subscribe()
+    }
+
+    fn observe_service(&self, service: &ServiceState) {
+        self.metrics.occupancy.set(service.len() as i64);
+        self.metrics.capacity.set(service.capacity() as i64);
+    }
+
+    /// Returns `true` if the inner proof of the state commits to [INNER_VK_B32] and to the
+    /// configured domains and strictness.
+    ///
+    /// States proven by a previous circuit, including the legacy encodings, are decoded but can't
+    /// be extended by recursion. States proven for another domain set or strictness would either
+    /// be rejected by the circuit or silently keep their previous guarantees.
+    fn is_current(&self, state: &State) -> bool {
+        let domains = Circuit::domains_commitment::<Sp1Hasher>(&self.domains);
+
+        state
+            .proof
+            .decode()
+            .ok()
+            .and_then(|(_, inputs)| CircuitOutput::decode(&inputs).ok())
+            .is_some_and(|o| {
+                o.vk == INNER_VK_B32 && o.domains == domains && o.strict == self.strict
+            })
+    }
+
     pub async fn init(self) -> anyhow::Result<Self> {
         tracing::info!("Loading controller `{}`...", self.id);
 
+        let storage = self.storage.clone();
+        let persisted = tokio::task::spawn_blocking(move || storage.load()).await??;
+
+        tracing::info!("Loaded `{}` persisted states...", persisted.len());
+
+        let (persisted, stale): (Vec<_>, Vec<_>) =
+            persisted.into_iter().partition(|s| self.is_current(s));
+
+        if !stale.is_empty() {
+            tracing::warn!(
+                "discarding `{}` states proven by a previous circuit or for other domains...",
+                stale.len()
+            );
+        }
+
+        {
+            let mut service = self.service.lock().await;
+
+            for s in persisted {
+                service.insert(s);
+            }
+
+            self.observe_service(&service);
+        }
+
+        // the history is flushed with the states, so it is loaded first
+        if self.history.is_persistent() {
+            let storage = self.storage.clone();
+            let entries = tokio::task::spawn_blocking(move || storage.load_history()).await??;
+
+            tracing::info!("Loaded `{}` persisted historical entries...", entries.len());
+
+            self.history.extend(entries);
+        }
+
+        self.flush_states().await?;
+
         let state = self.coprocessor.get_storage_raw(&self.id).await;
 
         tracing::info!("Data present on the co-processor: {}...", state.is_ok());
@@ -86,29 +419,82 @@ impl App {
             state.is_some()
         );
 
+        // states of a previous circuit or domain set can't be extended by the current one, so the
+        // chain is bootstrapped again from the initial root
+        let state = state.filter(|s| {
+            let current = self.is_current(s);
+
+            if !current {
+                tracing::warn!(
+                    "Co-processor state proven by a previous circuit or for other domains; ignoring..."
+                );
+            }
+
+            current
+        });
+
         let state = match state {
             Some(s) => {
-                self.service.lock().await.insert(s.clone());
+                let known = {
+                    let mut service = self.service.lock().await;
+                    let known = service.get(&s.update.uuid).is_some();
+
+                    service.insert(s.clone());
+                    self.observe_service(&service);
+                    known
+                };
+
+                if !known {
+                    self.persist_state(&s).await;
+                }
+
+                self.pipeline.set_published(s.update.uuid).await;
+
                 s
             }
-            None => {
-                tracing::info!("Data not available; bootstrapping...");
-
-                let input = CircuitInput::default().pack_to_vec();
-                let proof = self.prover.get_sp1_proof(
-                    self.inner_hash,
-                    ProofType::Compressed,
-                    &input,
-                    &[],
-                    |_| Ok(INNER_ELF.to_vec()),
-                )?;
-
-                self.publish_wrapper_proof(proof).await?
-            }
+            None => match self.latest().await {
+                Some(s) => {
+                    tracing::info!("Data not available; resuming from persisted state...");
+
+                    s
+                }
+                None => {
+                    tracing::info!("Data not available; bootstrapping...");
+
+                    let input = CircuitInput {
+                        vk: INNER_VK_B32.to_vec(),
+                        domains: self.domains.clone(),
+                        strict: self.strict,
+                        ..Default::default()
+                    }
+                    .pack_to_vec();
+                    let proof = self
+                        .get_sp1_proof(
+                            self.inner_hash,
+                            ProofType::Compressed,
+                            input,
+                            Vec::new(),
+                            INNER_ELF,
+                        )
+                        .await?;
+
+                    let metadata = StateMetadata {
+                        proved_at: timestamp(),
+                        ..Default::default()
+                    };
+
+                    self.verify_inner(&proof, &Circuit::INITIAL_ROOT)
+                        .await
+                        .context(FailureClass::InvalidUpdate)?;
+                    self.publish_wrapper_proof(proof, metadata).await?
+                }
+            },
         };
 
         tracing::info!("State `{}` loaded...", hex::encode(&state.update.root));
 
+        self.health.succeeded();
+
         Ok(self)
     }
 
@@ -116,19 +502,76 @@ impl App {
         self.service.lock().await.latest().cloned()
     }
 
-    pub async fn insert_state(&self, proof: Proof, wrapper: Proof) -> anyhow::Result<State> {
+    pub async fn state(&self, root: &Hash) -> Option<State> {
+        self.service.lock().await.get_by_root(root).cloned()
+    }
+
+    pub async fn state_by_uuid(&self, uuid: &[u8; 16]) -> Option<State> {
+        self.service.lock().await.get(uuid).cloned()
+    }
+
+    /// Returns up to `limit` cached states with a UUID greater than `after`, oldest first, and
+    /// the cursor of the next page, if any.
+    ///
+    /// The limit is capped to `MAX_PAGE_SIZE`.
+    pub async fn states_page(
+        &self,
+        after: Option<[u8; 16]>,
+        limit: usize,
+    ) -> (Vec<State>, Option<[u8; 16]>) {
+        let limit = limit.clamp(1, MAX_PAGE_SIZE);
+        let service = self.service.lock().await;
+        let lower = after.map(Bound::Excluded).unwrap_or(Bound::Unbounded);
+        let mut states: Vec<_> = service
+            .range((lower, Bound::Unbounded))
+            .take(limit + 1)
+            .cloned()
+            .collect();
+
+        let next = if states.len() > limit {
+            states.truncate(limit);
+            states.last().map(|s| s.update.uuid)
+        } else {
+            None
+        };
+
+        (states, next)
+    }
+
+    pub async fn insert_state(
+        &self,
+        proof: Proof,
+        wrapper: Proof,
+        metadata: StateMetadata,
+    ) -> anyhow::Result<State> {
+        self.insert_state_with(proof, wrapper, metadata, true).await
+    }
+
+    /// Inserts the state; if `publish`, publishes it when latest.
+    ///
+    /// The intermediate chunks of a range are not published, as the final state supersedes them.
+    async fn insert_state_with(
+        &self,
+        proof: Proof,
+        wrapper: Proof,
+        metadata: StateMetadata,
+        publish: bool,
+    ) -> anyhow::Result<State> {
         tracing::debug!("inserting new state...");
 
-        let root = wrapper.decode()?.1;
-        let root = Hash::try_from(root.as_slice())?;
+        let root = self
+            .verify_wrapper(&proof, &wrapper)
+            .await
+            .context(FailureClass::InvalidUpdate)?;
 
         tracing::debug!("root computed...");
 
-        let update = self.coprocessor.get_historical_update(&root).await?;
+        let update = self.historical_update(&root).await?;
         let state = State {
             update,
             proof,
             wrapper,
+            metadata,
         };
 
         tracing::debug!("new state computed...");
@@ -137,135 +580,498 @@ impl App {
             let mut service = self.service.lock().await;
 
             service.insert(state.clone());
-            service.latest().filter(|l| *l < &state).is_none()
+            self.observe_service(&service);
+            service.latest() == Some(&state)
         };
 
-        if should_update {
+        self.persist_state(&state).await;
+
+        let mut published = false;
+
+        if should_update && publish {
             tracing::info!(
                 "produced latest update `{}`; publishing...",
                 hex::encode(state.update.root)
             );
 
-            let bytes = serde_json::to_vec(&state)?;
-            let updated = self.coprocessor.set_storage_raw(&self.id, bytes).await;
-
-            match updated {
-                Ok(true) => tracing::info!("co-processor updated."),
-                Ok(false) => tracing::warn!("co-processor not updated."),
+            match self.publish_state(&state, false).await {
+                Ok(Publication::Published) => {
+                    tracing::info!("co-processor updated.");
+                    published = true;
+                }
+                Ok(Publication::Skipped) => tracing::info!("newer state already published."),
+                Ok(Publication::Rejected) => tracing::warn!("co-processor not updated."),
                 Err(e) => tracing::warn!("co-processor not updated: {e}"),
             }
         }
 
+        // no subscribers is not an error
+        self.events
+            .send(StateEvent {
+                state: state.clone(),
+                latest: should_update,
+                published,
+            })
+            .ok();
+
         Ok(state)
     }
 
-    pub async fn compute_inner_proof(&self, root: &Hash) -> anyhow::Result<Option<Proof>> {
+    pub async fn compute_inner_proof(
+        &self,
+        root: &Hash,
+    ) -> anyhow::Result<Option<(Proof, StateMetadata)>> {
+        self.compute_inner_proof_with(root, false).await
+    }
+
+    /// Computes the inner proof of `root`; if `force`, ignores the cached proof of `root` itself.
+    async fn compute_inner_proof_with(
+        &self,
+        root: &Hash,
+        force: bool,
+    ) -> anyhow::Result<Option<(Proof, StateMetadata)>> {
         tracing::debug!("computing inner proof for `{}`...", hex::encode(root));
 
-        let update = self.coprocessor.get_historical_update(&root).await?;
-        let state = {
-            self.service
-                .lock()
-                .await
-                .get_lower_bound(update.uuid)
-                .cloned()
-                .ok_or_else(|| anyhow::anyhow!("failed to find the lower bound for the proof"))?
+        let update = self.historical_update(root).await?;
+        let bound = if force {
+            u128::from_be_bytes(update.uuid)
+                .checked_sub(1)
+                .ok_or_else(|| anyhow::anyhow!("the initial root cannot be re-proven"))?
+                .to_be_bytes()
+        } else {
+            update.uuid
         };
 
-        let state_root = state.root()?;
+        let state = self.service.lock().await.get_lower_bound(bound).cloned();
+
+        let state = match state {
+            Some(s) => Some((
+                s.update.uuid,
+                InnerProof {
+                    root: s.root()?,
+                    proof: s.proof,
+                    metadata: s.metadata,
+                },
+            )),
+            None => None,
+        };
 
-        tracing::debug!("lower bound state: `{}`...", hex::encode(state_root));
+        // in-flight inner proofs are valid lower bounds, even if not wrapped yet
+        let pending = self.pipeline.get_lower_bound(bound).await;
+        let base = state
+            .into_iter()
+            .chain(pending)
+            .max_by_key(|(uuid, _)| *uuid)
+            .map(|(_, b)| b)
+            .ok_or_else(|| {
+                anyhow::anyhow!("failed to find the lower bound for the proof")
+                    .context(FailureClass::MissingLowerBound)
+            })?;
 
-        let proof = state.proof.clone();
-        let from = state_root;
+        tracing::debug!("lower bound state: `{}`...", hex::encode(base.root));
+
+        let from = base.root;
         let to = *root;
 
         if to == from {
             tracing::debug!("cache hit.");
 
-            return Ok(Some(proof));
+            self.metrics.cache.with_label_values(&["hit"]).inc();
+
+            return Ok(Some((base.proof, base.metadata)));
         }
 
+        self.metrics.cache.with_label_values(&["miss"]).inc();
+
         tracing::debug!(
             "cache miss; fetching updates from `{}` to `{}`...",
             hex::encode(from),
             hex::encode(to)
         );
 
-        let updates = self.coprocessor.get_historical_updates(&from, &to).await?;
+        let updates = self.historical_updates(&from, &to).await?;
         if updates.is_empty() {
             tracing::debug!("no updates available.");
 
             return Ok(None);
         }
 
-        tracing::debug!("got `{}` updates, proving inner...", updates.len());
+        tracing::debug!("got `{}` updates, executing natively...", updates.len());
+
+        self.preflight(&base.root, &updates).await?;
+
+        tracing::debug!("updates accepted, proving inner...");
+
+        let mut base = base;
+        let mut updates = updates;
+
+        while updates.len() > self.max_updates {
+            let remaining = updates.split_off(self.max_updates);
+            let chunk = mem::replace(&mut updates, remaining);
+            let root = chunk.last().map(|u| u.update.root).unwrap_or(base.root);
+
+            tracing::debug!(
+                "proving chunk of `{}` updates up to `{}`...",
+                chunk.len(),
+                hex::encode(root)
+            );
+
+            let uuid = chunk.last().map(|u| u.update.uuid).unwrap_or_default();
+            let (proof, metadata) = self.prove_updates(base, chunk).await?;
+
+            base = InnerProof {
+                root,
+                proof,
+                metadata,
+            };
+
+            self.spawn_intermediate(uuid, base.clone()).await;
+        }
+
+        self.prove_updates(base, updates).await.map(Some)
+    }
+
+    /// Executes the inner circuit natively on `updates`, so an invalid batch fails before the
+    /// remote prover is called.
+    async fn preflight(
+        &self,
+        base: &Hash,
+        updates: &[HistoricalTransitionProof],
+    ) -> anyhow::Result<()> {
+        let circuit = Circuit {
+            initial_root: *base,
+            domains: self.domains.clone(),
+            strict: self.strict,
+        };
+
+        let result = match updates.first() {
+            Some(u) if &u.update.previous != base => Err(anyhow::anyhow!("unexpected root")
+                .context(UpdateFailure {
+                    index: 0,
+                    domain: u.update.block.domain,
+                })),
+            _ if !self.preflight => Ok(()),
+            _ => {
+                let updates = updates.to_vec();
+
+                tokio::task::spawn_blocking(move || {
+                    circuit
+                        .root::<Sp1Hasher, DeferredRecursion>(updates)
+                        .map(|_| ())
+                })
+                .await?
+            }
+        };
+
+        if let Err(e) = &result {
+            if let Some(f) = FailedUpdate::of(e) {
+                tracing::warn!(
+                    "update `{}` of domain `{}` rejected: {}",
+                    f.index,
+                    f.domain,
+                    f.reason
+                );
+            }
+        }
+
+        result.context(FailureClass::InvalidUpdate)
+    }
+
+    /// Returns the compressed light-client proofs of the elected [ProofSystem::Sp1Compressed]
+    /// domains, in update order, to be verified by the circuit as deferred proofs.
+    fn light_client_proofs(
+        &self,
+        updates: &[HistoricalTransitionProof],
+    ) -> anyhow::Result<Vec<DeferredProof>> {
+        updates
+            .iter()
+            .enumerate()
+            .filter(|(_, u)| {
+                self.domains.iter().any(|d| {
+                    d.id == u.update.block.domain && d.system == ProofSystem::Sp1Compressed
+                })
+            })
+            .map(|(index, u)| {
+                let domain = u.update.block.domain;
+                let CompressedPayload { proof, vk } =
+                    CompressedPayload::unpack(&u.update.block.payload)
+                        .context(UpdateFailure { index, domain })?
+                        .1;
+
+                Ok(DeferredProof { proof, vk })
+            })
+            .collect()
+    }
+
+    /// Computes an inner proof of `updates`, recursing on `base`.
+    async fn prove_updates(
+        &self,
+        base: InnerProof,
+        updates: Vec<HistoricalTransitionProof>,
+    ) -> anyhow::Result<(Proof, StateMetadata)> {
+        self.metrics.batch_updates.observe(updates.len() as f64);
+
+        let root = updates.last().map(|u| u.update.root).unwrap_or(base.root);
+
+        let mut metadata = StateMetadata {
+            domains: Circuit {
+                initial_root: base.root,
+                domains: self.domains.clone(),
+                strict: self.strict,
+            }
+            .stats(&updates),
+            height: base.metadata.height + updates.len() as u64,
+            ..Default::default()
+        };
+
+        let previous = base.proof.decode()?.1;
+        let mut recursive = vec![DeferredProof::inner(base.proof)];
+
+        recursive.extend(
+            self.light_client_proofs(&updates)
+                .context(FailureClass::InvalidUpdate)?,
+        );
 
         let input = CircuitInput {
             vk: INNER_VK_B32.to_vec(),
             updates,
+            domains: self.domains.clone(),
+            strict: self.strict,
+            previous,
         }
         .pack_to_vec();
 
-        let proof = RecursiveProof::try_from_compressed_proof(&state.proof, self.inner.vk.clone())?;
+        let start = Instant::now();
+        let proof = self
+            .get_sp1_proof(
+                self.inner_hash,
+                ProofType::Compressed,
+                input,
+                recursive,
+                INNER_ELF,
+            )
+            .await?;
+
+        self.metrics
+            .inner_duration
+            .observe(start.elapsed().as_secs_f64());
 
-        let proof = self.prover.get_sp1_proof(
-            self.inner_hash,
-            ProofType::Compressed,
-            &input,
-            &[proof],
-            |_| Ok(INNER_ELF.to_vec()),
-        )?;
+        metadata.proved_at = timestamp();
 
         tracing::debug!("inner proof computed.");
 
-        Ok(Some(proof))
+        self.verify_inner(&proof, &root)
+            .await
+            .context(FailureClass::InvalidUpdate)?;
+
+        Ok((proof, metadata))
     }
 
-    pub async fn publish_wrapper_proof(&self, proof: Proof) -> anyhow::Result<State> {
+    pub async fn publish_wrapper_proof(
+        &self,
+        proof: Proof,
+        metadata: StateMetadata,
+    ) -> anyhow::Result<State> {
+        self.wrap_inner_proof(proof, metadata, true).await
+    }
+
+    /// Computes the wrapper proof and inserts the state; if `publish`, publishes it when latest.
+    pub(crate) async fn wrap_inner_proof(
+        &self,
+        proof: Proof,
+        mut metadata: StateMetadata,
+        publish: bool,
+    ) -> anyhow::Result<State> {
         let inputs = proof.decode()?.1;
-        let recursive = RecursiveProof::try_from_compressed_proof(&proof, self.inner.vk.clone())?;
 
-        let wrapper = self.prover.get_sp1_proof(
-            self.wrapper_hash,
-            ProofType::Groth16,
-            &inputs,
-            &[recursive],
-            |_| Ok(WRAPPER_ELF.to_vec()),
-        )?;
+        let start = Instant::now();
+        let wrapper = self
+            .get_sp1_proof(
+                self.wrapper_hash,
+                ProofType::Groth16,
+                inputs,
+                vec![DeferredProof::inner(proof.clone())],
+                WRAPPER_ELF,
+            )
+            .await
+            .context(FailureClass::Wrapper)?;
+
+        self.metrics
+            .wrapper_duration
+            .observe(start.elapsed().as_secs_f64());
+
+        metadata.wrapped_at = timestamp();
 
         tracing::debug!("computed wrapper proof; publishing...");
 
-        self.insert_state(proof, wrapper).await
+        self.insert_state_with(proof, wrapper, metadata, publish)
+            .await
     }
 
-    pub async fn update_to_latest(&self) -> anyhow::Result<Option<State>> {
-        tracing::debug!("checking for recent historical root...");
+    /// Verifies the inner proof, checking it commits to `root` and [INNER_VK_B32].
+    async fn verify_inner(&self, proof: &Proof, root: &Hash) -> anyhow::Result<()> {
+        let output = CircuitOutput::decode(&proof.decode()?.1)?;
 
-        let root = self.coprocessor.get_historical().await?;
-        let update = self.coprocessor.get_historical_update(&root).await?;
+        if &output.root != root {
+            tracing::error!(
+                "inner proof root mismatch: expected `{}`, got `{}`",
+                hex::encode(root),
+                hex::encode(output.root)
+            );
 
-        tracing::debug!("got latest update `{}`...", hex::encode(update.root));
+            anyhow::bail!("inner proof root mismatch");
+        }
 
-        match self.latest().await {
-            Some(l) if update.uuid <= l.update.uuid => {
-                tracing::debug!("already up-to-date; skipping...");
-                return Ok(None);
-            }
-            None => {
-                tracing::error!("failed to fetch latest update!");
-            }
-            _ => tracing::debug!("proceed with proof computation..."),
+        if output.vk != INNER_VK_B32 {
+            tracing::error!("inner proof vk mismatch: got `{}`", hex::encode(output.vk));
+
+            anyhow::bail!("inner proof vk mismatch");
         }
 
-        let proof = match self.compute_inner_proof(&root).await? {
-            Some(p) => p,
-            None => {
-                tracing::debug!("no inner proof available; skipping...");
-                return Ok(None);
+        let verifier = self.verifier.clone();
+        let proof = proof.clone();
+
+        tokio::task::spawn_blocking(move || verifier.verify_inner(&proof))
+            .await?
+            .inspect_err(|e| {
+                tracing::error!("inner proof rejected: {e}");
+            })
+    }
+
+    /// Verifies the wrapper of the inner proof, returning the committed root.
+    async fn verify_wrapper(&self, proof: &Proof, wrapper: &Proof) -> anyhow::Result<Hash> {
+        let inputs = proof.decode()?.1;
+        let outputs = wrapper.decode()?.1;
+
+        let (expected, vk) = inputs
+            .split_at_checked(inputs.len().saturating_sub(INNER_VK_B32.len()))
+            .ok_or_else(|| anyhow::anyhow!("invalid inner proof public values"))?;
+
+        if vk != INNER_VK_B32 {
+            tracing::error!("wrapped proof vk mismatch: got `{}`", hex::encode(vk));
+
+            anyhow::bail!("wrapped proof vk mismatch");
+        }
+
+        if outputs != expected {
+            tracing::error!(
+                "wrapper public values mismatch: expected `{}`, got `{}`",
+                hex::encode(expected),
+                hex::encode(&outputs)
+            );
+
+            anyhow::bail!("wrapper public values mismatch");
+        }
+
+        let verifier = self.verifier.clone();
+        let wrapper = wrapper.clone();
+
+        tokio::task::spawn_blocking(move || verifier.verify_wrapper(&wrapper))
+            .await?
+            .inspect_err(|e| {
+                tracing::error!("wrapper proof rejected: {e}");
+            })?;
+
+        Ok(WrapperOutput::decode(&outputs)?.root)
+    }
+
+    /// Requests a proof from the prover without blocking the async runtime.
+    async fn get_sp1_proof(
+        &self,
+        circuit: Hash,
+        proof_type: ProofType,
+        input: Vec<u8>,
+        recursive: Vec<DeferredProof>,
+        elf: &'static [u8],
+    ) -> anyhow::Result<Proof> {
+        let prover = self.prover.clone();
+
+        tokio::task::spawn_blocking(move || {
+            prover.get_sp1_proof(circuit, proof_type, &input, &recursive, elf)
+        })
+        .await?
+    }
+
+    /// Enqueues a proving job for the provided historical root, returning the job id.
+    ///
+    /// Fails once the shutdown started, or if the job queue is full.
+    pub async fn prove(&self, root: Hash) -> anyhow::Result<u64> {
+        self.enqueue(root, false).await
+    }
+
+    /// Enqueues a proving job for the provided historical root, even if already cached.
+    pub async fn reprove(&self, root: Hash) -> anyhow::Result<u64> {
+        self.enqueue(root, true).await
+    }
+
+    async fn enqueue(&self, root: Hash, force: bool) -> anyhow::Result<u64> {
+        anyhow::ensure!(!self.is_closing(), "the service is shutting down");
+
+        let id = self
+            .jobs
+            .create()
+            .await
+            .ok_or_else(|| anyhow::anyhow!("the job queue is full"))?;
+        let app = self.clone();
+
+        tracing::info!("job `{id}` queued for root `{}`...", hex::encode(root));
+
+        tokio::spawn(async move {
+            let status = match app.run_job(id, &root, force).await {
+                Ok(state) => JobStatus::Done {
+                    root: hex::encode(state.update.root),
+                    uuid: hex::encode(state.update.uuid),
+                },
+                Err(e) => {
+                    tracing::warn!("job `{id}` failed: {e:#}");
+
+                    JobStatus::Failed {
+                        reason: format!("{e:#}"),
+                        update: FailedUpdate::of(&e),
+                    }
+                }
+            };
+
+            app.jobs.set(id, status).await;
+        });
+
+        Ok(id)
+    }
+
+    /// Returns the status of a proving job.
+    pub async fn job(&self, id: u64) -> Option<JobStatus> {
+        self.jobs.get(id).await
+    }
+
+    async fn run_job(&self, id: u64, root: &Hash, force: bool) -> anyhow::Result<State> {
+        let _permit = self.jobs.permits().acquire_owned().await?;
+
+        match self.state(root).await {
+            Some(state) if !force => {
+                tracing::debug!("job `{id}` cache hit.");
+
+                return Ok(state);
             }
-        };
+            _ => (),
+        }
+
+        self.jobs.set(id, JobStatus::ProvingInner).await;
+
+        let (proof, metadata) = self
+            .compute_inner_proof_with(root, force)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("no updates available for the provided root"))?;
 
-        self.publish_wrapper_proof(proof).await.map(Some)
+        self.jobs.set(id, JobStatus::Wrapping).await;
+
+        self.publish_wrapper_proof(proof, metadata).await
     }
 }
+
+/// Returns the current Unix timestamp, in seconds.
+fn timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
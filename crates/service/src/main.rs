@@ -1,56 +1,168 @@
-use std::{net::SocketAddr, time::Duration};
+use std::{net::SocketAddr, path::PathBuf, time::Duration};
 
+use anyhow::Context as _;
 use clap::Parser;
-use poem::{http::StatusCode, listener::TcpListener, web::Data, EndpointExt as _, Route};
-use poem_openapi::{payload::Json, OpenApi, OpenApiService};
+use poem::{
+    http::StatusCode,
+    listener::TcpListener,
+    web::{
+        sse::{Event, SSE},
+        Data,
+    },
+    EndpointExt as _, IntoResponse as _, Response, Route,
+};
+use poem_openapi::{
+    auth::Bearer,
+    param::{Path, Query},
+    payload::Json,
+    ApiResponse, OpenApi, OpenApiService, SecurityScheme,
+};
+use serde::Serialize;
 use serde_json::{json, Value};
-use tokio::time::sleep;
+use tokio::{signal, time::sleep};
+use tokio_stream::{
+    wrappers::{errors::BroadcastStreamRecvError, BroadcastStream},
+    StreamExt as _,
+};
 use tracing_subscriber::{fmt, layer::SubscriberExt as _, util::SubscriberInitExt as _, EnvFilter};
-use valence_coprocessor_domain_prover_service::App;
+use valence_coprocessor::Hash;
+use valence_coprocessor_domain_prover::State;
+use valence_coprocessor_domain_prover_service::{
+    App, Config, FileStorage, HistoryCache, StateSummary, Webhooks,
+};
 
 #[derive(Parser)]
 struct Cli {
+    /// Path to a TOML configuration file. Keys can be overridden by `DOMAIN_PROVER_<KEY>`
+    /// environment variables, then by the flags below.
+    #[arg(long, value_name = "CONFIG")]
+    config: Option<PathBuf>,
+
+    /// Prints the effective configuration and exits.
+    #[arg(long)]
+    print_config: bool,
+
     /// Bind to the provided socket
-    #[arg(short, long, value_name = "SOCKET", default_value = "0.0.0.0:37279")]
-    bind: SocketAddr,
+    #[arg(short, long, value_name = "SOCKET")]
+    bind: Option<SocketAddr>,
 
     /// Address to the co-processor service backend.
-    #[arg(
-        short,
-        long,
-        value_name = "COPROCESSOR",
-        default_value = "https://service.coprocessor.valence.zone"
-    )]
-    coprocessor: String,
+    #[arg(short, long, value_name = "COPROCESSOR")]
+    coprocessor: Option<String>,
 
     /// Address to the prover service backend.
-    #[arg(
-        short,
-        long,
-        value_name = "PROVER",
-        default_value = "wss://prover.coprocessor.valence.zone"
-    )]
-    prover: String,
+    #[arg(short, long, value_name = "PROVER")]
+    prover: Option<String>,
 
     /// Cache capacity
-    #[arg(long, value_name = "CAPACITY", default_value_t = 1000)]
-    capacity: usize,
+    #[arg(long, value_name = "CAPACITY")]
+    capacity: Option<usize>,
 
     /// Update interval (ms)
-    #[arg(long, value_name = "INTERVAL", default_value_t = 60000)]
-    interval: u64,
+    #[arg(long, value_name = "INTERVAL")]
+    interval: Option<u64>,
+
+    /// Path to a JSON file with the elected domains. Defaults to the build-time set.
+    #[arg(long, value_name = "DOMAINS")]
+    domains: Option<PathBuf>,
+
+    /// Rejects updates of domains that are not elected; `--strict=false` disables it.
+    #[arg(long, value_name = "BOOL", num_args = 0..=1, default_missing_value = "true")]
+    strict: Option<bool>,
+
+    /// Maximum number of wrapper proofs computed concurrently with the next inner proof.
+    #[arg(long, value_name = "MAX_WRAPPERS")]
+    max_wrappers: Option<usize>,
+
+    /// Maximum number of historical updates per inner proof. Unbounded if omitted.
+    #[arg(long, value_name = "MAX_UPDATES")]
+    max_updates: Option<usize>,
+
+    /// Webhook target notified of published states and failures. Can be repeated.
+    #[arg(long = "webhook", value_name = "URL")]
+    webhooks: Vec<String>,
+
+    /// HMAC secret used to sign the webhook bodies.
+    #[arg(long, value_name = "SECRET")]
+    webhook_secret: Option<String>,
+
+    /// Path to a JSON lines file of the undelivered webhook notifications.
+    #[arg(long, value_name = "DEAD_LETTER")]
+    webhook_dead_letter: Option<PathBuf>,
+
+    /// Consecutive update failures between two `update_failed` webhook notifications.
+    #[arg(long, value_name = "WEBHOOK_FAILURE_THRESHOLD")]
+    webhook_failure_threshold: Option<u64>,
+
+    /// Number of update intervals without a successful update before the service is not ready.
+    #[arg(long, value_name = "READY_INTERVALS")]
+    ready_intervals: Option<u32>,
+
+    /// Maximum time to wait for the in-flight proofs on shutdown (s)
+    #[arg(long, value_name = "SHUTDOWN_TIMEOUT")]
+    shutdown_timeout: Option<u64>,
+
+    /// Consecutive update failures before the service is reported as degraded.
+    #[arg(long, value_name = "FAILURE_THRESHOLD")]
+    failure_threshold: Option<u64>,
+
+    /// Path to a file-backed storage for the computed states. Memory-only if omitted.
+    #[arg(long, value_name = "STORAGE")]
+    storage: Option<PathBuf>,
+
+    /// Maximum number of historical updates and transition proofs cached. Disabled if zero.
+    #[arg(long, value_name = "HISTORY_CAPACITY")]
+    history_capacity: Option<usize>,
+
+    /// Persists the historical fetch cache alongside the states; `--persist-history=false` disables it.
+    #[arg(long, value_name = "BOOL", num_args = 0..=1, default_missing_value = "true")]
+    persist_history: Option<bool>,
+
+    /// Bearer token of the admin API. The admin API is disabled if omitted.
+    #[arg(long, value_name = "TOKEN")]
+    admin_token: Option<String>,
+}
+
+impl Cli {
+    /// Loads the configuration file, overriding it with the provided flags.
+    fn into_config(self) -> anyhow::Result<(Config, bool)> {
+        let mut config = Config::load(self.config.as_ref())?;
+
+        config.bind = self.bind.unwrap_or(config.bind);
+        config.coprocessor = self.coprocessor.unwrap_or(config.coprocessor);
+        config.prover = self.prover.unwrap_or(config.prover);
+        config.capacity = self.capacity.unwrap_or(config.capacity);
+        config.interval = self.interval.unwrap_or(config.interval);
+        config.max_wrappers = self.max_wrappers.unwrap_or(config.max_wrappers);
+        config.ready_intervals = self.ready_intervals.unwrap_or(config.ready_intervals);
+        config.shutdown_timeout = self.shutdown_timeout.unwrap_or(config.shutdown_timeout);
+        config.failure_threshold = self.failure_threshold.unwrap_or(config.failure_threshold);
+        config.webhook_failure_threshold = self
+            .webhook_failure_threshold
+            .unwrap_or(config.webhook_failure_threshold);
+        config.history_capacity = self.history_capacity.unwrap_or(config.history_capacity);
+        config.strict = self.strict.unwrap_or(config.strict);
+        config.persist_history = self.persist_history.unwrap_or(config.persist_history);
+
+        config.domains = self.domains.or(config.domains);
+        config.max_updates = self.max_updates.or(config.max_updates);
+        config.webhook_secret = self.webhook_secret.or(config.webhook_secret);
+        config.webhook_dead_letter = self.webhook_dead_letter.or(config.webhook_dead_letter);
+        config.storage = self.storage.or(config.storage);
+        config.admin_token = self.admin_token.or(config.admin_token);
+
+        if !self.webhooks.is_empty() {
+            config.webhooks = self.webhooks;
+        }
+
+        config.validate()?;
+
+        Ok((config, self.print_config))
+    }
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let Cli {
-        bind,
-        coprocessor,
-        prover,
-        capacity,
-        interval,
-    } = Cli::parse();
-
     let filter_layer = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
     let fmt_layer = fmt::layer().with_target(false);
 
@@ -59,13 +171,88 @@ async fn main() -> anyhow::Result<()> {
         .with(fmt_layer)
         .init();
 
+    let (config, print_config) = Cli::parse().into_config()?;
+
+    if print_config {
+        print!("{}", config.to_toml()?);
+        return Ok(());
+    }
+
+    let Config {
+        bind,
+        coprocessor,
+        prover,
+        capacity,
+        eviction,
+        interval,
+        domains,
+        strict,
+        max_wrappers,
+        max_updates,
+        webhooks,
+        webhook_secret,
+        webhook_dead_letter,
+        webhook_failure_threshold,
+        ready_intervals,
+        shutdown_timeout,
+        failure_threshold,
+        storage,
+        history_capacity,
+        persist_history,
+        admin_token,
+    } = config;
+
     tracing::info!("loading app...");
 
-    let app = App::new(capacity)
+    let mut app = App::new(capacity)
+        .with_eviction(eviction)
         .with_coprocessor(coprocessor)
         .with_prover(prover)
-        .init()
-        .await?;
+        .with_strict(strict)
+        .with_max_wrappers(max_wrappers)
+        .with_failure_threshold(failure_threshold)
+        .with_history(HistoryCache::new(history_capacity).with_persistence(persist_history))
+        .with_max_staleness(Duration::from_millis(interval) * ready_intervals);
+
+    if let Some(path) = domains {
+        tracing::info!("loading elected domains from `{}`...", path.display());
+
+        let domains = std::fs::read(&path)
+            .with_context(|| format!("reading domains file {}", path.display()))?;
+        let domains = serde_json::from_slice(&domains)
+            .with_context(|| format!("parsing domains file {}", path.display()))?;
+
+        app = app.with_domains(domains);
+    }
+
+    if let Some(max_updates) = max_updates {
+        app = app.with_max_updates(max_updates);
+    }
+
+    if !webhooks.is_empty() {
+        tracing::info!("notifying `{}` webhook targets...", webhooks.len());
+
+        let mut webhooks =
+            Webhooks::new(webhooks).with_failure_threshold(webhook_failure_threshold);
+
+        if let Some(secret) = webhook_secret {
+            webhooks = webhooks.with_secret(secret);
+        }
+
+        if let Some(path) = webhook_dead_letter {
+            webhooks = webhooks.with_dead_letter(path);
+        }
+
+        app = app.with_webhooks(webhooks);
+    }
+
+    if let Some(path) = storage {
+        tracing::info!("using file storage `{}`...", path.display());
+
+        app = app.with_storage(FileStorage::open(path)?);
+    }
+
+    let app = app.init().await?;
 
     let latest = app
         .latest()
@@ -81,36 +268,229 @@ async fn main() -> anyhow::Result<()> {
     tokio::spawn(async move {
         let interval = Duration::from_millis(interval);
 
-        loop {
+        while !app_spawn.is_closing() {
             tracing::debug!("state update to latest...");
 
-            if let Err(e) = app_spawn.update_to_latest().await {
-                tracing::error!("error updating state: {e}");
-            }
+            let delay = app_spawn.background_update(interval).await;
 
-            sleep(interval).await;
+            sleep(delay).await;
         }
     });
 
-    let api_service = OpenApiService::new(Api, env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+    if admin_token.is_none() {
+        tracing::info!("admin token not set; admin API disabled...");
+    }
+
+    let api = (Api, AdminApi { token: admin_token });
+    let api_service = OpenApiService::new(api, env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
         .server(format!("http://{}/api", &bind));
 
+    let app_shutdown = app.clone();
+    let shutdown = async move {
+        shutdown_signal().await;
+
+        tracing::info!("shutdown requested...");
+
+        if let Err(e) = app_shutdown
+            .shutdown(Duration::from_secs(shutdown_timeout))
+            .await
+        {
+            tracing::error!("failed to flush states: {e}");
+        }
+    };
+
     let app = Route::new()
         .nest("/", api_service.swagger_ui())
         .nest("/spec", api_service.spec_endpoint())
         .nest("/spec/yaml", api_service.spec_endpoint_yaml())
         .nest("/api", api_service)
+        .at("/metrics", poem::get(metrics))
+        .at("/subscribe", poem::get(subscribe))
+        .at("/health", poem::get(health))
+        .at("/ready", poem::get(ready))
         .data(app);
 
     tracing::info!("API loaded, listening on `{}`...", &bind);
 
-    poem::Server::new(TcpListener::bind(&bind)).run(app).await?;
+    poem::Server::new(TcpListener::bind(&bind))
+        .run_with_graceful_shutdown(app, shutdown, Some(Duration::from_secs(10)))
+        .await?;
+
+    tracing::info!("API stopped.");
 
     Ok(())
 }
 
+/// Resolves on `SIGINT` or `SIGTERM`.
+async fn shutdown_signal() {
+    let terminate = async {
+        match signal::unix::signal(signal::unix::SignalKind::terminate()) {
+            Ok(mut s) => {
+                s.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("failed to listen for SIGTERM: {e}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    tokio::select! {
+        _ = signal::ctrl_c() => (),
+        _ = terminate => (),
+    }
+}
+
+/// Returns the Prometheus metrics of the service.
+#[poem::handler]
+async fn metrics(Data(app): Data<&App>) -> poem::Result<Response> {
+    let body = app
+        .metrics()
+        .encode()
+        .map_err(|_| poem::Error::from_status(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok(Response::builder()
+        .content_type(app.metrics().format_type())
+        .body(body))
+}
+
+/// Liveness probe; answers as long as the process serves requests.
+#[poem::handler]
+async fn health() -> poem::web::Json<Value> {
+    poem::web::Json(json!({
+        "status": "alive",
+    }))
+}
+
+/// Readiness probe; answers `503` until the service can serve fresh proofs.
+#[poem::handler]
+async fn ready(Data(app): Data<&App>) -> poem::Result<Response> {
+    let readiness = app.readiness(Duration::from_secs(5)).await;
+    let status = if readiness.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    let readiness = serde_json::to_value(readiness)
+        .map_err(|_| poem::Error::from_status(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok(poem::web::Json(readiness)
+        .with_status(status)
+        .into_response())
+}
+
+/// Streams the states accepted by the service as server-sent events.
+///
+/// Emits `state` events with the state and its `latest` and `published` flags, and `lagged`
+/// events with the number of skipped states if the client falls behind.
+#[poem::handler]
+async fn subscribe(Data(app): Data<&App>) -> SSE {
+    let events = BroadcastStream::new(app.subscribe()).filter_map(|e| match e {
+        Ok(e) => match serde_json::to_string(&e) {
+            Ok(e) => Some(Event::message(e).event_type("state")),
+            Err(e) => {
+                tracing::error!("failed to serialize state event: {e}");
+                None
+            }
+        },
+        Err(BroadcastStreamRecvError::Lagged(n)) => {
+            Some(Event::message(n.to_string()).event_type("lagged"))
+        }
+    });
+
+    SSE::new(events).keep_alive(Duration::from_secs(15))
+}
+
 pub struct Api;
 
+#[derive(ApiResponse)]
+pub enum StateResponse {
+    /// The cached state.
+    #[oai(status = 200)]
+    Ok(Json<Value>),
+
+    /// The state was evicted or never proven.
+    #[oai(status = 404)]
+    NotFound(Json<Value>),
+}
+
+impl StateResponse {
+    fn from_summary(state: Option<State>, key: &str, value: &str) -> poem::Result<Self> {
+        let summary = state
+            .as_ref()
+            .map(StateSummary::new)
+            .transpose()
+            .map_err(|e| {
+                tracing::error!("failed to decode state: {e}");
+
+                poem::Error::from_status(StatusCode::INTERNAL_SERVER_ERROR)
+            })?;
+
+        Self::from_state(summary, key, value)
+    }
+
+    fn from_state<T: Serialize>(state: Option<T>, key: &str, value: &str) -> poem::Result<Self> {
+        let state = match state {
+            Some(s) => s,
+            None => {
+                return Ok(Self::NotFound(Json(json!({
+                    "error": "state not found",
+                    key: value,
+                }))))
+            }
+        };
+
+        let state = serde_json::to_value(state).map_err(|e| {
+            tracing::error!("failed to serialize state: {e}");
+
+            poem::Error::from_status(StatusCode::INTERNAL_SERVER_ERROR)
+        })?;
+
+        Ok(Self::Ok(Json(state)))
+    }
+}
+
+#[derive(ApiResponse)]
+pub enum JobResponse {
+    /// The job status.
+    #[oai(status = 200)]
+    Ok(Json<Value>),
+
+    /// The job is unknown or was discarded.
+    #[oai(status = 404)]
+    NotFound(Json<Value>),
+}
+
+/// Default number of states of a `/states` page.
+const PAGE_SIZE: usize = 100;
+
+/// Lists a state by its update and proof timestamps.
+fn state_entry(state: &State) -> Value {
+    json!({
+        "uuid": hex::encode(state.update.uuid),
+        "root": hex::encode(state.update.root),
+        "domain": hex::encode(state.update.block.domain),
+        "number": state.update.block.number,
+        "proved_at": state.metadata.proved_at,
+        "wrapped_at": state.metadata.wrapped_at,
+    })
+}
+
+fn decode_hex<const N: usize>(value: &str) -> poem::Result<[u8; N]> {
+    let value = value.strip_prefix("0x").unwrap_or(value);
+
+    hex::decode(value)
+        .ok()
+        .and_then(|v| <[u8; N]>::try_from(v).ok())
+        .ok_or_else(|| {
+            poem::Error::from_string(
+                format!("expected a {N} bytes hex value"),
+                StatusCode::BAD_REQUEST,
+            )
+        })
+}
+
 #[OpenApi]
 impl Api {
     /// Returns the latest domain proof.
@@ -129,12 +509,269 @@ impl Api {
         Ok(Json(state))
     }
 
+    /// Returns a summary of the latest domain proof, with its public values decoded.
+    #[oai(path = "/latest/summary", method = "get")]
+    pub async fn latest_summary(&self, app: Data<&App>) -> poem::Result<StateResponse> {
+        let state = app.latest().await;
+
+        StateResponse::from_summary(state, "state", "latest")
+    }
+
+    /// Returns the cached domain proof for the provided historical root.
+    #[oai(path = "/state/:root", method = "get")]
+    pub async fn state(&self, app: Data<&App>, root: Path<String>) -> poem::Result<StateResponse> {
+        let hash: Hash = decode_hex(&root)?;
+        let state = app.state(&hash).await;
+
+        StateResponse::from_state(state, "root", &root)
+    }
+
+    /// Returns a summary of the cached domain proof for the provided historical root.
+    #[oai(path = "/state/:root/summary", method = "get")]
+    pub async fn state_summary(
+        &self,
+        app: Data<&App>,
+        root: Path<String>,
+    ) -> poem::Result<StateResponse> {
+        let hash: Hash = decode_hex(&root)?;
+        let state = app.state(&hash).await;
+
+        StateResponse::from_summary(state, "root", &root)
+    }
+
+    /// Returns the cached domain proof for the provided historical update UUID.
+    #[oai(path = "/state/uuid/:uuid", method = "get")]
+    pub async fn state_by_uuid(
+        &self,
+        app: Data<&App>,
+        uuid: Path<String>,
+    ) -> poem::Result<StateResponse> {
+        let id: [u8; 16] = decode_hex(&uuid)?;
+        let state = app.state_by_uuid(&id).await;
+
+        StateResponse::from_state(state, "uuid", &uuid)
+    }
+
+    /// Lists the cached states with a UUID greater than `after`, oldest first.
+    ///
+    /// `next` is the `after` cursor of the following page, or `null` on the last page.
+    #[oai(path = "/states", method = "get")]
+    pub async fn states(
+        &self,
+        app: Data<&App>,
+        after: Query<Option<String>>,
+        limit: Query<Option<usize>>,
+    ) -> poem::Result<Json<Value>> {
+        let after = after.as_deref().map(decode_hex).transpose()?;
+        let limit = limit.unwrap_or(PAGE_SIZE);
+        let (states, next) = app.states_page(after, limit).await;
+        let states: Vec<_> = states.iter().map(state_entry).collect();
+
+        Ok(Json(json!({
+            "states": states,
+            "next": next.map(hex::encode),
+        })))
+    }
+
+    /// Enqueues a proving job for the provided historical root.
+    ///
+    /// Answers `503` while shutting down or if the job queue is full.
+    #[oai(path = "/prove/:root", method = "post")]
+    pub async fn prove(&self, app: Data<&App>, root: Path<String>) -> poem::Result<Json<Value>> {
+        let root: Hash = decode_hex(&root)?;
+        let job = app.prove(root).await.map_err(|e| {
+            poem::Error::from_string(e.to_string(), StatusCode::SERVICE_UNAVAILABLE)
+        })?;
+
+        Ok(Json(json!({
+            "job": job,
+        })))
+    }
+
+    /// Returns the status of a proving job.
+    #[oai(path = "/jobs/:id", method = "get")]
+    pub async fn job(&self, app: Data<&App>, id: Path<u64>) -> poem::Result<JobResponse> {
+        let status = match app.job(*id).await {
+            Some(s) => s,
+            None => {
+                return Ok(JobResponse::NotFound(Json(json!({
+                    "error": "job not found",
+                    "job": *id,
+                }))))
+            }
+        };
+
+        let status = serde_json::to_value(status)
+            .map_err(|_| poem::Error::from_status(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+        Ok(JobResponse::Ok(Json(status)))
+    }
+
+    /// Returns the health of the background updates.
+    ///
+    /// The service is `degraded` once the consecutive failures reach the threshold.
+    #[oai(path = "/health", method = "get")]
+    pub async fn health(&self, app: Data<&App>) -> poem::Result<Json<Value>> {
+        let health = serde_json::to_value(app.health())
+            .map_err(|_| poem::Error::from_status(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+        Ok(Json(health))
+    }
+
+    /// Returns the lookup statistics of the historical fetch cache.
+    #[oai(path = "/history", method = "get")]
+    pub async fn history(&self, app: Data<&App>) -> poem::Result<Json<Value>> {
+        let stats = serde_json::to_value(app.history())
+            .map_err(|_| poem::Error::from_status(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+        Ok(Json(stats))
+    }
+
     /// Returns the domain proof constants.
     #[oai(path = "/consts", method = "get")]
     pub async fn consts(&self, app: Data<&App>) -> poem::Result<Json<Value>> {
         Ok(Json(json!({
             "id": app.id(),
             "vk": app.vk(),
+            "domains": app.domains(),
+            "strict": app.strict(),
+        })))
+    }
+}
+
+/// Bearer token of the admin API.
+#[derive(SecurityScheme)]
+#[oai(ty = "bearer")]
+pub struct AdminAuth(Bearer);
+
+pub struct AdminApi {
+    token: Option<String>,
+}
+
+impl AdminApi {
+    /// Rejects the request unless the admin API is enabled and the token matches.
+    fn authorize(&self, auth: &AdminAuth) -> poem::Result<()> {
+        let token = self.token.as_deref().unwrap_or_default().as_bytes();
+        let bearer = auth.0.token.as_bytes();
+
+        // constant time comparison, as the length is not secret
+        let matches = token.len() == bearer.len()
+            && token
+                .iter()
+                .zip(bearer)
+                .fold(0, |acc, (a, b)| acc | (a ^ b))
+                == 0;
+
+        if self.token.is_none() || !matches {
+            return Err(poem::Error::from_status(StatusCode::UNAUTHORIZED));
+        }
+
+        Ok(())
+    }
+}
+
+fn internal_error(e: anyhow::Error) -> poem::Error {
+    tracing::error!("admin request failed: {e:#}");
+
+    poem::Error::from_string(format!("{e:#}"), StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[OpenApi(prefix_path = "/admin")]
+impl AdminApi {
+    /// Lists the cached states, oldest first.
+    #[oai(path = "/states", method = "get")]
+    pub async fn states(&self, auth: AdminAuth, app: Data<&App>) -> poem::Result<Json<Value>> {
+        self.authorize(&auth)?;
+
+        let states: Vec<_> = app.states().await.iter().map(state_entry).collect();
+
+        Ok(Json(json!({
+            "capacity": app.capacity().await,
+            "states": states,
+        })))
+    }
+
+    /// Evicts the cached state of the provided historical update UUID.
+    #[oai(path = "/states/:uuid", method = "delete")]
+    pub async fn evict(
+        &self,
+        auth: AdminAuth,
+        app: Data<&App>,
+        uuid: Path<String>,
+    ) -> poem::Result<StateResponse> {
+        self.authorize(&auth)?;
+
+        let id: [u8; 16] = decode_hex(&uuid)?;
+        let state = app.evict(&id).await.map_err(internal_error)?;
+
+        StateResponse::from_summary(state, "uuid", &uuid)
+    }
+
+    /// Enqueues a proving job for the current co-processor head.
+    #[oai(path = "/update", method = "post")]
+    pub async fn update(&self, auth: AdminAuth, app: Data<&App>) -> poem::Result<Json<Value>> {
+        self.authorize(&auth)?;
+
+        let job = app.prove_head().await.map_err(internal_error)?;
+
+        Ok(Json(json!({
+            "job": job,
+        })))
+    }
+
+    /// Enqueues a proving job for the provided historical root, even if already cached.
+    #[oai(path = "/prove/:root", method = "post")]
+    pub async fn reprove(
+        &self,
+        auth: AdminAuth,
+        app: Data<&App>,
+        root: Path<String>,
+    ) -> poem::Result<Json<Value>> {
+        self.authorize(&auth)?;
+
+        let root: Hash = decode_hex(&root)?;
+        let job = app.reprove(root).await.map_err(internal_error)?;
+
+        Ok(Json(json!({
+            "job": job,
+        })))
+    }
+
+    /// Publishes the cached state of the provided UUID to the co-processor storage.
+    #[oai(path = "/republish/:uuid", method = "post")]
+    pub async fn republish(
+        &self,
+        auth: AdminAuth,
+        app: Data<&App>,
+        uuid: Path<String>,
+    ) -> poem::Result<StateResponse> {
+        self.authorize(&auth)?;
+
+        let id: [u8; 16] = decode_hex(&uuid)?;
+        let published = app
+            .republish(&id)
+            .await
+            .map_err(internal_error)?
+            .map(|publication| json!({ "publication": publication }));
+
+        StateResponse::from_state(published, "uuid", &uuid)
+    }
+
+    /// Updates the cache capacity, evicting the states in excess per the eviction policy.
+    #[oai(path = "/capacity/:capacity", method = "put")]
+    pub async fn capacity(
+        &self,
+        auth: AdminAuth,
+        app: Data<&App>,
+        capacity: Path<usize>,
+    ) -> poem::Result<Json<Value>> {
+        self.authorize(&auth)?;
+
+        let evicted = app.set_capacity(*capacity).await.map_err(internal_error)?;
+
+        Ok(Json(json!({
+            "capacity": app.capacity().await,
+            "evicted": evicted,
         })))
     }
 }
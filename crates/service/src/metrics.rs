@@ -0,0 +1,179 @@
+use prometheus::{
+    exponential_buckets, Encoder as _, Histogram, HistogramOpts, IntCounterVec, IntGauge, Opts,
+    Registry, TextEncoder,
+};
+
+use crate::Lag;
+
+/// Prometheus metrics of the service.
+#[derive(Debug, Clone)]
+pub struct Metrics {
+    registry: Registry,
+
+    /// Duration of the inner (compressed) proofs, in seconds.
+    pub inner_duration: Histogram,
+
+    /// Duration of the wrapper (Groth16) proofs, in seconds.
+    pub wrapper_duration: Histogram,
+
+    /// Number of historical updates per inner proof.
+    pub batch_updates: Histogram,
+
+    /// Lower bound lookups of the inner proof computation, by `result` (`hit` or `miss`).
+    pub cache: IntCounterVec,
+
+    /// Historical fetch cache lookups, by `result` (`hit` or `miss`).
+    ///
+    /// Shared with the [HistoryCache](crate::HistoryCache), which records them.
+    pub history: IntCounterVec,
+
+    /// Co-processor storage updates, by `result` (`success` or `failure`).
+    pub publish: IntCounterVec,
+
+    /// Failed background updates, by `class`.
+    pub failures: IntCounterVec,
+
+    /// Historical updates between the latest proven update and the co-processor head.
+    pub lag: IntGauge,
+
+    /// Seconds since the co-processor head was first seen ahead of the latest proven update.
+    pub lag_seconds: IntGauge,
+
+    /// Number of cached states.
+    pub occupancy: IntGauge,
+
+    /// Maximum number of cached states.
+    pub capacity: IntGauge,
+}
+
+/// Creates the counter of the historical fetch cache lookups.
+pub(crate) fn history_lookups() -> prometheus::Result<IntCounterVec> {
+    IntCounterVec::new(
+        Opts::new(
+            "domain_prover_history_cache_total",
+            "Historical fetch cache lookups.",
+        ),
+        &["result"],
+    )
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new().unwrap()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> anyhow::Result<Self> {
+        let registry = Registry::new();
+
+        let inner_duration = Histogram::with_opts(
+            HistogramOpts::new(
+                "domain_prover_inner_proof_duration_seconds",
+                "Duration of the inner proofs.",
+            )
+            .buckets(exponential_buckets(1.0, 2.0, 12)?),
+        )?;
+
+        let wrapper_duration = Histogram::with_opts(
+            HistogramOpts::new(
+                "domain_prover_wrapper_proof_duration_seconds",
+                "Duration of the wrapper proofs.",
+            )
+            .buckets(exponential_buckets(1.0, 2.0, 12)?),
+        )?;
+
+        let batch_updates = Histogram::with_opts(
+            HistogramOpts::new(
+                "domain_prover_batch_updates",
+                "Number of historical updates per inner proof.",
+            )
+            .buckets(exponential_buckets(1.0, 2.0, 14)?),
+        )?;
+
+        let cache = IntCounterVec::new(
+            Opts::new(
+                "domain_prover_cache_total",
+                "Lower bound lookups of the inner proof computation.",
+            ),
+            &["result"],
+        )?;
+
+        let history = history_lookups()?;
+
+        let publish = IntCounterVec::new(
+            Opts::new(
+                "domain_prover_publish_total",
+                "Co-processor storage updates.",
+            ),
+            &["result"],
+        )?;
+
+        let failures = IntCounterVec::new(
+            Opts::new(
+                "domain_prover_update_failures_total",
+                "Failed background updates.",
+            ),
+            &["class"],
+        )?;
+
+        let lag = IntGauge::new(
+            "domain_prover_update_lag",
+            "Historical updates between the latest proven update and the co-processor head.",
+        )?;
+        let lag_seconds = IntGauge::new(
+            "domain_prover_lag_seconds",
+            "Seconds since the co-processor head was first seen ahead of the latest proven update.",
+        )?;
+
+        let occupancy = IntGauge::new("domain_prover_states", "Number of cached states.")?;
+        let capacity = IntGauge::new(
+            "domain_prover_states_capacity",
+            "Maximum number of cached states.",
+        )?;
+
+        registry.register(Box::new(inner_duration.clone()))?;
+        registry.register(Box::new(wrapper_duration.clone()))?;
+        registry.register(Box::new(batch_updates.clone()))?;
+        registry.register(Box::new(cache.clone()))?;
+        registry.register(Box::new(history.clone()))?;
+        registry.register(Box::new(publish.clone()))?;
+        registry.register(Box::new(failures.clone()))?;
+        registry.register(Box::new(lag.clone()))?;
+        registry.register(Box::new(lag_seconds.clone()))?;
+        registry.register(Box::new(occupancy.clone()))?;
+        registry.register(Box::new(capacity.clone()))?;
+
+        Ok(Self {
+            registry,
+            inner_duration,
+            wrapper_duration,
+            batch_updates,
+            cache,
+            history,
+            publish,
+            failures,
+            lag,
+            lag_seconds,
+            occupancy,
+            capacity,
+        })
+    }
+
+    /// Returns the content type of the encoded metrics.
+    pub fn format_type(&self) -> String {
+        TextEncoder::new().format_type().to_string()
+    }
+
+    /// Encodes the metrics in the Prometheus text format.
+    pub fn encode(&self) -> anyhow::Result<String> {
+        Ok(TextEncoder::new().encode_to_string(&self.registry.gather())?)
+    }
+
+    /// Sets the lag between the latest proven update and the co-processor head.
+    pub fn set_lag(&self, lag: &Lag) {
+        self.lag.set(i64::try_from(lag.updates).unwrap_or(i64::MAX));
+        self.lag_seconds
+            .set(i64::try_from(lag.seconds).unwrap_or(i64::MAX));
+    }
+}
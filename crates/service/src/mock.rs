@@ -0,0 +1,313 @@
+//! In-process co-processor and prover backends for offline testing.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use async_trait::async_trait;
+use msgpacker::Unpackable as _;
+use valence_coprocessor::{Hash, HistoricalTransitionProof, HistoricalUpdate, Proof};
+use valence_coprocessor_domain_prover::{
+    Circuit, CircuitInput, CircuitOutput, DomainBlock, ProofSystem, WrapperOutput,
+};
+use valence_coprocessor_prover::types::ProofType;
+use valence_coprocessor_sp1::Sp1Hasher;
+
+use crate::{Coprocessor, DeferredProof, Prover, Verifier};
+
+#[derive(Debug)]
+struct MockChain {
+    storage: HashMap<String, Vec<u8>>,
+    updates: Vec<HistoricalUpdate>,
+    fetches: usize,
+}
+
+/// A co-processor with an in-memory historical chain and controller storage.
+///
+/// The chain starts with an update producing [Circuit::INITIAL_ROOT].
+#[derive(Debug, Clone)]
+pub struct MockCoprocessor {
+    chain: Arc<Mutex<MockChain>>,
+}
+
+impl Default for MockCoprocessor {
+    fn default() -> Self {
+        let genesis = HistoricalUpdate {
+            root: Circuit::INITIAL_ROOT,
+            ..Default::default()
+        };
+
+        let chain = MockChain {
+            storage: HashMap::new(),
+            updates: vec![genesis],
+            fetches: 0,
+        };
+
+        Self {
+            chain: Arc::new(Mutex::new(chain)),
+        }
+    }
+}
+
+impl MockCoprocessor {
+    /// Appends a new block of `domain` to the historical chain, returning the new root.
+    pub fn push(&self, domain: Hash, number: u64) -> Hash {
+        self.push_with_payload(domain, number, Vec::new())
+    }
+
+    /// Appends a new block of `domain` with the provided light-client payload.
+    pub fn push_with_payload(&self, domain: Hash, number: u64, payload: Vec<u8>) -> Hash {
+        let mut chain = self.chain.lock().unwrap();
+
+        let uuid = (chain.updates.len() as u128).to_be_bytes();
+        let previous = chain.updates.last().map(|u| u.root).unwrap_or_default();
+
+        let mut root = [0xffu8; 32];
+
+        root[..16].copy_from_slice(&uuid);
+
+        let mut update = HistoricalUpdate {
+            uuid,
+            previous,
+            root,
+            ..Default::default()
+        };
+
+        update.block.domain = domain;
+        update.block.number = number;
+        update.block.payload = payload;
+
+        chain.updates.push(update);
+
+        root
+    }
+
+    /// Returns the raw storage of the controller, if set.
+    pub fn storage(&self, controller: &str) -> Option<Vec<u8>> {
+        self.chain.lock().unwrap().storage.get(controller).cloned()
+    }
+
+    /// Returns the number of historical updates and ranges fetched.
+    pub fn fetches(&self) -> usize {
+        self.chain.lock().unwrap().fetches
+    }
+
+    fn fetched(&self) {
+        self.chain.lock().unwrap().fetches += 1;
+    }
+
+    fn update(&self, root: &Hash) -> anyhow::Result<(usize, HistoricalUpdate)> {
+        self.chain
+            .lock()
+            .unwrap()
+            .updates
+            .iter()
+            .enumerate()
+            .find(|(_, u)| &u.root == root)
+            .map(|(i, u)| (i, u.clone()))
+            .ok_or_else(|| anyhow::anyhow!("historical update not found"))
+    }
+}
+
+#[async_trait]
+impl Coprocessor for MockCoprocessor {
+    async fn get_storage_raw(&self, controller: &str) -> anyhow::Result<Vec<u8>> {
+        self.storage(controller)
+            .ok_or_else(|| anyhow::anyhow!("controller storage not found"))
+    }
+
+    async fn set_storage_raw(&self, controller: &str, data: Vec<u8>) -> anyhow::Result<bool> {
+        self.chain
+            .lock()
+            .unwrap()
+            .storage
+            .insert(controller.to_string(), data);
+
+        Ok(true)
+    }
+
+    async fn get_historical(&self) -> anyhow::Result<Hash> {
+        self.chain
+            .lock()
+            .unwrap()
+            .updates
+            .last()
+            .map(|u| u.root)
+            .ok_or_else(|| anyhow::anyhow!("empty historical chain"))
+    }
+
+    async fn get_historical_update(&self, root: &Hash) -> anyhow::Result<HistoricalUpdate> {
+        self.fetched();
+
+        Ok(self.update(root)?.1)
+    }
+
+    async fn get_historical_updates(
+        &self,
+        from: &Hash,
+        to: &Hash,
+    ) -> anyhow::Result<Vec<HistoricalTransitionProof>> {
+        self.fetched();
+
+        let (from, _) = self.update(from)?;
+        let (to, _) = self.update(to)?;
+
+        if to <= from {
+            return Ok(Vec::new());
+        }
+
+        let updates = self.chain.lock().unwrap().updates[from + 1..=to]
+            .iter()
+            .cloned()
+            .map(|update| HistoricalTransitionProof {
+                update,
+                ..Default::default()
+            })
+            .collect();
+
+        Ok(updates)
+    }
+}
+
+/// A prover that computes the circuits public values natively and returns SP1 mock proofs,
+/// without verifying the light-client proofs nor the recursive proofs.
+///
+/// The inner circuit expects the previous proof followed by a deferred proof per elected
+/// [ProofSystem::Sp1Compressed] block.
+#[derive(Debug, Default, Clone)]
+pub struct MockProver {
+    calls: Arc<AtomicUsize>,
+}
+
+impl MockProver {
+    /// Returns the number of proofs computed.
+    pub fn calls(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+
+    fn inner(input: &[u8], recursive: &[DeferredProof]) -> anyhow::Result<Vec<u8>> {
+        let input = CircuitInput::unpack(input)?.1;
+        let domains = Circuit::domains_commitment::<Sp1Hasher>(&input.domains);
+
+        if input.updates.is_empty() {
+            let output = CircuitOutput {
+                root: Circuit::INITIAL_ROOT,
+                domains,
+                strict: input.strict,
+                blocks: Vec::new(),
+                vk: input.vk,
+            };
+
+            return Ok(output.encode());
+        }
+
+        let deferred = input
+            .updates
+            .iter()
+            .filter(|u| {
+                input.domains.iter().any(|d| {
+                    d.id == u.update.block.domain && d.system == ProofSystem::Sp1Compressed
+                })
+            })
+            .count();
+
+        anyhow::ensure!(
+            recursive.len() == 1 + deferred,
+            "expected `{}` recursive proofs",
+            1 + deferred
+        );
+
+        let previous = CircuitOutput::decode(&input.previous)?;
+
+        anyhow::ensure!(previous.root == input.initial_root(), "unexpected root");
+        anyhow::ensure!(previous.domains == domains, "unexpected domains");
+        anyhow::ensure!(previous.vk == input.vk, "unexpected vk");
+
+        let mut root = previous.root;
+        let mut blocks = Vec::new();
+
+        for u in &input.updates {
+            anyhow::ensure!(u.update.previous == root, "unexpected root");
+
+            root = u.update.root;
+
+            if input.domains.iter().any(|d| d.id == u.update.block.domain) {
+                blocks.push(DomainBlock {
+                    id: u.update.block.domain,
+                    number: u.update.block.number,
+                    root: u.update.block.root,
+                });
+            } else {
+                anyhow::ensure!(!input.strict, "domain not elected");
+            }
+        }
+
+        let output = CircuitOutput {
+            root,
+            domains,
+            strict: previous.strict && input.strict,
+            blocks: DomainBlock::merge(&previous.blocks, &blocks),
+            vk: input.vk,
+        };
+
+        Ok(output.encode())
+    }
+
+    fn wrapper(input: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let CircuitOutput {
+            root,
+            domains,
+            strict,
+            blocks,
+            ..
+        } = CircuitOutput::decode(input)?;
+
+        let output = WrapperOutput {
+            root,
+            domains,
+            strict,
+            blocks,
+        };
+
+        Ok(output.encode())
+    }
+}
+
+impl Prover for MockProver {
+    fn get_sp1_proof(
+        &self,
+        _circuit: Hash,
+        proof_type: ProofType,
+        input: &[u8],
+        recursive: &[DeferredProof],
+        _elf: &'static [u8],
+    ) -> anyhow::Result<Proof> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+
+        let inputs = if matches!(proof_type, ProofType::Groth16) {
+            Self::wrapper(input)?
+        } else {
+            Self::inner(input, recursive)?
+        };
+
+        Ok(Proof::new(Vec::new(), inputs))
+    }
+}
+
+/// A verifier that accepts the SP1 mock proofs, leaving only the public values checks.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MockVerifier;
+
+impl Verifier for MockVerifier {
+    fn verify_inner(&self, _proof: &Proof) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn verify_wrapper(&self, _proof: &Proof) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,323 @@
+use std::{
+    collections::BTreeMap,
+    ops::Bound,
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
+
+use serde::Serialize;
+use tokio::{
+    sync::{Mutex, Semaphore},
+    task::JoinHandle,
+};
+use valence_coprocessor::{Hash, Proof};
+use valence_coprocessor_domain_prover::{State, StateMetadata};
+
+use crate::{App, Notification};
+
+/// The outcome of publishing a state to the co-processor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Publication {
+    /// The co-processor storage was updated with the state.
+    Published,
+
+    /// A newer state was already published.
+    Skipped,
+
+    /// The co-processor declined the storage update.
+    Rejected,
+}
+
+/// An inner proof with its computed root.
+#[derive(Debug, Clone)]
+pub struct InnerProof {
+    pub root: Hash,
+    pub proof: Proof,
+    pub metadata: StateMetadata,
+}
+
+/// Tracks the inner proofs awaiting their wrapper so the next inner proof can start before the
+/// previous wrapper is done.
+#[derive(Debug, Clone)]
+pub struct Pipeline {
+    pending: Arc<Mutex<BTreeMap<[u8; 16], InnerProof>>>,
+    wrappers: Arc<Semaphore>,
+    max_wrappers: u32,
+    published: Arc<Mutex<Option<[u8; 16]>>>,
+    backoff: Arc<Mutex<Option<Duration>>>,
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+impl Pipeline {
+    /// Creates a pipeline with at most `max_wrappers` wrapper proofs in flight.
+    pub fn new(max_wrappers: usize) -> Self {
+        let max_wrappers = u32::try_from(max_wrappers)
+            .unwrap_or(u32::MAX)
+            .clamp(1, Semaphore::MAX_PERMITS as u32);
+
+        Self {
+            pending: Default::default(),
+            wrappers: Arc::new(Semaphore::new(max_wrappers as usize)),
+            max_wrappers,
+            published: Default::default(),
+            backoff: Default::default(),
+        }
+    }
+
+    /// Returns the pending inner proof with the highest UUID not greater than `uuid`.
+    pub async fn get_lower_bound(&self, uuid: [u8; 16]) -> Option<([u8; 16], InnerProof)> {
+        self.pending
+            .lock()
+            .await
+            .range((Bound::Unbounded, Bound::Included(uuid)))
+            .next_back()
+            .map(|(u, p)| (*u, p.clone()))
+    }
+
+    /// Returns the UUID of the most recent pending inner proof.
+    pub async fn latest(&self) -> Option<[u8; 16]> {
+        self.pending.lock().await.keys().next_back().copied()
+    }
+
+    /// Records the UUID of the state found on the co-processor, so older ones are not published.
+    pub(crate) async fn set_published(&self, uuid: [u8; 16]) {
+        self.published.lock().await.replace(uuid);
+    }
+}
+
+impl App {
+    /// Proves the latest historical root, scheduling its wrapper proof in the background.
+    ///
+    /// Returns the handle of the wrapper task, if a new root was found. Waits for a wrapper slot
+    /// only after the inner proof is computed, so it overlaps with in-flight wrappers.
+    pub async fn advance(&self) -> anyhow::Result<Option<JoinHandle<anyhow::Result<State>>>> {
+        if self.is_closing() {
+            tracing::debug!("shutting down; skipping...");
+            return Ok(None);
+        }
+
+        tracing::debug!("checking for recent historical root...");
+
+        let root = self.coprocessor.get_historical().await?;
+        let update = self.historical_update(&root).await?;
+        let uuid = update.uuid;
+
+        let latest = self.latest().await;
+        let pending = self.pipeline.latest().await;
+
+        if let Some(l) = &latest {
+            if let Err(e) = self.lag(l, &update).await {
+                tracing::warn!("failed to measure the lag: {e:#}; proving anyway...");
+            }
+        }
+
+        let latest = latest.map(|l| l.update.uuid);
+
+        if latest.max(pending).is_some_and(|l| uuid <= l) {
+            tracing::debug!("already up-to-date; skipping...");
+            return Ok(None);
+        }
+
+        let (proof, metadata) = match self.compute_inner_proof(&root).await? {
+            Some(p) => p,
+            None => {
+                tracing::debug!("no inner proof available; skipping...");
+                return Ok(None);
+            }
+        };
+
+        self.pipeline.pending.lock().await.insert(
+            uuid,
+            InnerProof {
+                root,
+                proof: proof.clone(),
+                metadata: metadata.clone(),
+            },
+        );
+
+        let permit = self.pipeline.wrappers.clone().acquire_owned().await?;
+        let app = self.clone();
+
+        tracing::debug!("scheduling wrapper for `{}`...", hex::encode(root));
+
+        Ok(Some(tokio::spawn(async move {
+            let state = app.publish_wrapper_proof(proof, metadata).await;
+
+            app.pipeline.pending.lock().await.remove(&uuid);
+
+            drop(permit);
+
+            state.inspect_err(|e| {
+                tracing::error!("error computing wrapper for `{}`: {e:#}", hex::encode(root));
+            })
+        })))
+    }
+
+    /// Proves the latest historical root and waits for its wrapper proof.
+    ///
+    /// Returns the new state, if a new root was found.
+    pub async fn update_to_latest(&self) -> anyhow::Result<Option<State>> {
+        match self.advance().await? {
+            Some(wrapper) => wrapper.await?.map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Runs a background update, returning the delay before the next one.
+    ///
+    /// The wrapper task is recorded once done, so its failures reach the health report and the
+    /// webhooks instead of counting as a success. If it failed, the next update returns its
+    /// backoff instead of proving.
+    pub async fn background_update(&self, interval: Duration) -> Duration {
+        if let Some(backoff) = self.pipeline.backoff.lock().await.take() {
+            tracing::debug!("wrapper failed; backing off for {backoff:?}...");
+            return backoff;
+        }
+
+        let wrapper = match self.advance().await {
+            Ok(Some(w)) => w,
+            result => return self.record_update(&result, interval),
+        };
+
+        let app = self.clone();
+
+        tokio::spawn(async move {
+            let result = wrapper.await.map_err(anyhow::Error::from).and_then(|r| r);
+
+            let mut backoff = app.pipeline.backoff.lock().await;
+            let delay = app.record_update(&result, interval);
+
+            if result.is_err() {
+                backoff.replace(delay);
+            }
+        });
+
+        interval
+    }
+
+    /// Returns `true` once the shutdown started.
+    pub fn is_closing(&self) -> bool {
+        self.closing.load(Ordering::SeqCst)
+    }
+
+    /// Stops scheduling new proofs, waits up to `timeout` for the in-flight wrappers and jobs, and
+    /// flushes the cached states and the persistent historical entries to the storage.
+    pub async fn shutdown(&self, timeout: Duration) -> anyhow::Result<()> {
+        self.closing.store(true, Ordering::SeqCst);
+
+        tracing::info!("draining in-flight proofs...");
+
+        // jobs first, as their intermediate chunks take a wrapper slot
+        let drained = tokio::time::timeout(timeout, async {
+            let jobs = self.jobs.permits().acquire_owned().await?;
+            let wrappers = self
+                .pipeline
+                .wrappers
+                .acquire_many(self.pipeline.max_wrappers)
+                .await?;
+
+            anyhow::Ok((jobs, wrappers))
+        })
+        .await;
+
+        match drained {
+            Ok(Ok(_)) => tracing::info!("in-flight proofs drained."),
+            Ok(Err(e)) => tracing::warn!("failed to drain in-flight proofs: {e}"),
+            Err(_) => tracing::warn!("timeout draining in-flight proofs; discarding..."),
+        }
+
+        // late acquirers fail instead of starting new proofs
+        self.pipeline.wrappers.close();
+        self.jobs.permits().close();
+
+        let flushed = self.flush_states().await?;
+
+        tracing::info!("flushed `{flushed}` states.");
+
+        Ok(())
+    }
+
+    /// Wraps the inner proof of an intermediate chunk in the background, once a wrapper slot is
+    /// available, so the next chunk is proven meanwhile.
+    ///
+    /// The inner proof is pending until wrapped; the state is cached but not published.
+    pub(crate) async fn spawn_intermediate(&self, uuid: [u8; 16], inner: InnerProof) {
+        self.pipeline
+            .pending
+            .lock()
+            .await
+            .insert(uuid, inner.clone());
+
+        let app = self.clone();
+
+        tokio::spawn(async move {
+            let InnerProof {
+                root,
+                proof,
+                metadata,
+            } = inner;
+
+            let wrapped = match app.pipeline.wrappers.clone().acquire_owned().await {
+                Ok(_permit) => app.wrap_inner_proof(proof, metadata, false).await,
+                Err(e) => Err(e.into()),
+            };
+
+            app.pipeline.pending.lock().await.remove(&uuid);
+
+            if let Err(e) = wrapped {
+                tracing::warn!("failed to wrap intermediate `{}`: {e:#}", hex::encode(root));
+            }
+        });
+    }
+
+    /// Publishes the state to the co-processor, unless a newer state was already published.
+    ///
+    /// If `force`, publishes regardless, and later states are published again.
+    pub(crate) async fn publish_state(
+        &self,
+        state: &State,
+        force: bool,
+    ) -> anyhow::Result<Publication> {
+        let mut published = self.pipeline.published.lock().await;
+
+        if !force && published.is_some_and(|p| state.update.uuid <= p) {
+            tracing::debug!("newer state already published; skipping...");
+            return Ok(Publication::Skipped);
+        }
+
+        let bytes = serde_json::to_vec(state)?;
+        let updated = self.coprocessor.set_storage_raw(&self.id, bytes).await;
+        let result = match updated {
+            Ok(true) => "success",
+            _ => "failure",
+        };
+
+        self.metrics.publish.with_label_values(&[result]).inc();
+
+        match updated? {
+            true => {
+                published.replace(state.update.uuid);
+
+                self.webhooks.notify(Notification::Published {
+                    state: state.clone(),
+                });
+
+                Ok(Publication::Published)
+            }
+            false => {
+                self.webhooks.notify(Notification::PublishRejected {
+                    root: hex::encode(state.update.root),
+                    uuid: hex::encode(state.update.uuid),
+                });
+
+                Ok(Publication::Rejected)
+            }
+        }
+    }
+}
@@ -0,0 +1,317 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufReader, BufWriter, Read as _, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use msgpacker::{Packable, Unpackable};
+use valence_coprocessor_domain_prover::State;
+
+use crate::{App, HistoryEntry};
+
+/// Version of the [FileStorage] format, written as the first byte of every file.
+pub const STORAGE_VERSION: u8 = 2;
+
+/// Maximum size of a [FileStorage] record; larger length prefixes are treated as corrupt.
+pub const MAX_RECORD_SIZE: usize = 64 * 1024 * 1024;
+
+/// Default number of appended states before the [FileStorage] log is compacted.
+pub const COMPACTION_INTERVAL: usize = 256;
+
+/// A persistent backend for the service states.
+pub trait Storage: Send + Sync {
+    /// Loads all the persisted states.
+    fn load(&self) -> anyhow::Result<Vec<State>>;
+
+    /// Persists a newly inserted state.
+    fn persist(&self, state: &State) -> anyhow::Result<()>;
+
+    /// Replaces the persisted states with the provided set.
+    fn flush(&self, states: &[State]) -> anyhow::Result<()>;
+
+    /// Returns `true` if the persisted states should be replaced by the retained ones.
+    fn should_compact(&self) -> bool {
+        false
+    }
+
+    /// Loads the persisted historical fetch cache entries.
+    fn load_history(&self) -> anyhow::Result<Vec<HistoryEntry>> {
+        Ok(Vec::new())
+    }
+
+    /// Replaces the persisted historical fetch cache entries.
+    fn flush_history(&self, _entries: &[HistoryEntry]) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// A storage that keeps nothing; states live only in memory.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MemoryStorage;
+
+impl Storage for MemoryStorage {
+    fn load(&self) -> anyhow::Result<Vec<State>> {
+        Ok(Vec::new())
+    }
+
+    fn persist(&self, _state: &State) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn flush(&self, _states: &[State]) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// An append-only log of length-prefixed msgpack states, prefixed by [STORAGE_VERSION].
+///
+/// The log is compacted every `compaction` appended states. The historical fetch cache is kept
+/// next to it, with the `history` extension.
+#[derive(Debug)]
+pub struct FileStorage {
+    path: PathBuf,
+    file: Mutex<Log>,
+    compaction: usize,
+}
+
+#[derive(Debug)]
+struct Log {
+    file: File,
+    appended: usize,
+}
+
+impl FileStorage {
+    pub fn open<P: Into<PathBuf>>(path: P) -> anyhow::Result<Self> {
+        let path = path.into();
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+
+        if file.metadata()?.len() == 0 {
+            file.write_all(&[STORAGE_VERSION])?;
+            file.sync_all()?;
+        }
+
+        let file = Mutex::new(Log { file, appended: 0 });
+
+        Ok(Self {
+            path,
+            file,
+            compaction: COMPACTION_INTERVAL,
+        })
+    }
+
+    /// Compacts the log every `compaction` appended states.
+    pub fn with_compaction(mut self, compaction: usize) -> Self {
+        self.compaction = compaction.max(1);
+        self
+    }
+
+    fn lock(&self) -> anyhow::Result<std::sync::MutexGuard<'_, Log>> {
+        self.file
+            .lock()
+            .map_err(|_| anyhow::anyhow!("storage lock poisoned"))
+    }
+
+    fn history_path(&self) -> PathBuf {
+        self.path.with_extension("history")
+    }
+
+    fn write_record<T: Packable>(file: &mut impl Write, record: &T) -> anyhow::Result<()> {
+        let bytes = record.pack_to_vec();
+        let len = u32::try_from(bytes.len())?;
+
+        file.write_all(&len.to_le_bytes())?;
+        file.write_all(&bytes)?;
+
+        Ok(())
+    }
+
+    fn read_records<T: Unpackable>(path: &Path) -> anyhow::Result<Vec<T>>
+    where
+        T::Error: std::fmt::Display,
+    {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut records = Vec::new();
+        let mut version = [0u8; 1];
+
+        if reader.read_exact(&mut version).is_err() {
+            return Ok(records);
+        }
+
+        anyhow::ensure!(
+            version[0] == STORAGE_VERSION,
+            "unsupported storage version `{}` on `{}`; expected `{STORAGE_VERSION}`",
+            version[0],
+            path.display()
+        );
+
+        loop {
+            let mut len = [0u8; 4];
+
+            if reader.read_exact(&mut len).is_err() {
+                break;
+            }
+
+            let len = u32::from_le_bytes(len) as usize;
+
+            if len > MAX_RECORD_SIZE {
+                tracing::warn!(
+                    "corrupt record length `{len}` on `{}`; discarding the remainder...",
+                    path.display()
+                );
+                break;
+            }
+
+            let mut bytes = vec![0u8; len];
+
+            if reader.read_exact(&mut bytes).is_err() {
+                tracing::warn!("truncated record at the end of `{}`", path.display());
+                break;
+            }
+
+            match T::unpack(&bytes) {
+                Ok((_, r)) => records.push(r),
+                Err(e) => tracing::warn!("invalid record on `{}`: {e}", path.display()),
+            }
+        }
+
+        Ok(records)
+    }
+
+    fn write_records<T: Packable>(path: &Path, records: &[T]) -> anyhow::Result<()> {
+        let mut tmp = path.as_os_str().to_owned();
+
+        tmp.push(".tmp");
+
+        let tmp = PathBuf::from(tmp);
+
+        {
+            let mut writer = BufWriter::new(File::create(&tmp)?);
+
+            writer.write_all(&[STORAGE_VERSION])?;
+
+            for record in records {
+                Self::write_record(&mut writer, record)?;
+            }
+
+            writer.into_inner()?.sync_all()?;
+        }
+
+        fs::rename(&tmp, path)?;
+
+        Ok(())
+    }
+}
+
+impl Storage for FileStorage {
+    fn load(&self) -> anyhow::Result<Vec<State>> {
+        Self::read_records(&self.path)
+    }
+
+    fn persist(&self, state: &State) -> anyhow::Result<()> {
+        let mut log = self.lock()?;
+
+        // a single write, so a crash leaves at most one truncated record
+        let mut record = Vec::new();
+
+        Self::write_record(&mut record, state)?;
+
+        log.file.write_all(&record)?;
+        log.file.flush()?;
+        log.appended += 1;
+
+        Ok(())
+    }
+
+    fn flush(&self, states: &[State]) -> anyhow::Result<()> {
+        let mut log = self.lock()?;
+
+        Self::write_records(&self.path, states)?;
+
+        log.file = OpenOptions::new().append(true).open(&self.path)?;
+        log.appended = 0;
+
+        Ok(())
+    }
+
+    fn should_compact(&self) -> bool {
+        self.lock().is_ok_and(|l| l.appended >= self.compaction)
+    }
+
+    fn load_history(&self) -> anyhow::Result<Vec<HistoryEntry>> {
+        let path = self.history_path();
+
+        if !path.is_file() {
+            return Ok(Vec::new());
+        }
+
+        Self::read_records(&path)
+    }
+
+    fn flush_history(&self, entries: &[HistoryEntry]) -> anyhow::Result<()> {
+        Self::write_records(&self.history_path(), entries)
+    }
+}
+
+impl App {
+    /// Appends a newly inserted state to the storage, compacting it if needed.
+    ///
+    /// Failures are logged, as the state remains cached.
+    pub(crate) async fn persist_state(&self, state: &State) {
+        let storage = self.storage.clone();
+        let s = state.clone();
+        let persisted = {
+            let _writes = self.writes.lock().await;
+
+            tokio::task::spawn_blocking(move || {
+                storage.persist(&s)?;
+
+                anyhow::Ok(storage.should_compact())
+            })
+            .await
+        };
+
+        match persisted {
+            Ok(Ok(false)) => (),
+            Ok(Ok(true)) => {
+                tracing::debug!("compacting the storage...");
+
+                if let Err(e) = self.flush_states().await {
+                    tracing::warn!("failed to compact the storage: {e:#}");
+                }
+            }
+            Ok(Err(e)) => tracing::warn!("failed to persist state: {e:#}"),
+            Err(e) => tracing::warn!("failed to persist state: {e}"),
+        }
+    }
+
+    /// Replaces the persisted states with the cached ones, returning their count.
+    ///
+    /// The persistent historical entries are flushed along. The cache is released before writing
+    /// to the storage, but the appends are held until the storage is replaced, so a state inserted
+    /// after the snapshot is appended to the new log instead of being overwritten.
+    pub(crate) async fn flush_states(&self) -> anyhow::Result<usize> {
+        let _writes = self.writes.lock().await;
+        let retained: Vec<_> = self.service.lock().await.iter().cloned().collect();
+        let count = retained.len();
+        let history = self.history.is_persistent().then(|| self.history.entries());
+        let storage = self.storage.clone();
+
+        tokio::task::spawn_blocking(move || {
+            storage.flush(&retained)?;
+
+            match history {
+                Some(entries) => storage.flush_history(&entries),
+                None => Ok(()),
+            }
+        })
+        .await??;
+
+        Ok(count)
+    }
+}
@@ -0,0 +1,135 @@
+use serde::Serialize;
+use valence_coprocessor::Proof;
+use valence_coprocessor_domain_prover::{CircuitOutput, State, WrapperOutput};
+
+/// A human-readable view of a state, with its public values decoded.
+#[derive(Debug, Clone, Serialize)]
+pub struct StateSummary {
+    pub update: UpdateSummary,
+    pub output: OutputSummary,
+    pub proof: ProofSummary,
+    pub wrapper: ProofSummary,
+    pub domains: Vec<StatsSummary>,
+}
+
+/// The historical update fields of a state.
+#[derive(Debug, Clone, Serialize)]
+pub struct UpdateSummary {
+    pub uuid: String,
+    pub previous: String,
+    pub root: String,
+    pub domain: String,
+    pub number: u64,
+}
+
+/// The public values committed by the wrapper proof.
+#[derive(Debug, Clone, Serialize)]
+pub struct OutputSummary {
+    pub root: String,
+    pub domains: String,
+    pub strict: bool,
+    pub blocks: Vec<BlockSummary>,
+}
+
+/// The latest verified block of an elected domain.
+#[derive(Debug, Clone, Serialize)]
+pub struct BlockSummary {
+    pub id: String,
+    pub number: u64,
+    pub root: String,
+}
+
+/// The per-domain counters of the execution that produced the state.
+#[derive(Debug, Clone, Serialize)]
+pub struct StatsSummary {
+    pub id: String,
+    pub verified: u64,
+    pub skipped: u64,
+}
+
+/// A proof of the state.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProofSummary {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub size: usize,
+    pub public_values: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vk: Option<String>,
+    pub produced_at: u64,
+}
+
+impl ProofSummary {
+    fn new(kind: &'static str, proof: &Proof, produced_at: u64) -> anyhow::Result<Self> {
+        let (proof, inputs) = proof.decode()?;
+
+        Ok(Self {
+            kind,
+            size: proof.len(),
+            public_values: inputs.len(),
+            vk: None,
+            produced_at,
+        })
+    }
+}
+
+impl StateSummary {
+    pub fn new(state: &State) -> anyhow::Result<Self> {
+        let u = &state.update;
+        let update = UpdateSummary {
+            uuid: hex::encode(u.uuid),
+            previous: hex::encode(u.previous),
+            root: hex::encode(u.root),
+            domain: hex::encode(u.block.domain),
+            number: u.block.number,
+        };
+
+        let WrapperOutput {
+            root,
+            domains,
+            strict,
+            blocks,
+        } = state.output()?;
+
+        let output = OutputSummary {
+            root: hex::encode(root),
+            domains: hex::encode(domains),
+            strict,
+            blocks: blocks
+                .into_iter()
+                .map(|b| BlockSummary {
+                    id: hex::encode(b.id),
+                    number: b.number,
+                    root: hex::encode(b.root),
+                })
+                .collect(),
+        };
+
+        let inner = CircuitOutput::decode(&state.proof.decode()?.1)?;
+        let proof = ProofSummary {
+            vk: Some(hex::encode(inner.vk)),
+            ..ProofSummary::new("compressed", &state.proof, state.metadata.proved_at)?
+        };
+
+        let wrapper = ProofSummary::new("groth16", &state.wrapper, state.metadata.wrapped_at)?;
+
+        let domains = state
+            .metadata
+            .domains
+            .iter()
+            .map(|d| StatsSummary {
+                id: hex::encode(d.id),
+                verified: d.verified,
+                skipped: d.skipped,
+            })
+            .collect();
+
+        Ok(Self {
+            update,
+            output,
+            proof,
+            wrapper,
+            domains,
+        })
+    }
+}
@@ -0,0 +1,227 @@
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use hmac::{Hmac, Mac as _};
+use serde::Serialize;
+use serde_json::json;
+use sha2::Sha256;
+use tokio::{fs, io::AsyncWriteExt as _, sync::Mutex};
+use valence_coprocessor_domain_prover::State;
+
+/// Header carrying the hex HMAC-SHA256 of the body, prefixed by `sha256=`.
+pub const SIGNATURE_HEADER: &str = "X-Signature-256";
+
+/// Timeout to connect to a webhook target.
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Timeout of a single delivery attempt, including the response.
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Default number of consecutive update failures between two `update_failed` notifications.
+pub const WEBHOOK_FAILURE_THRESHOLD: u64 = 3;
+
+/// An event pushed to the webhook targets.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Notification {
+    /// A new latest state was published to the co-processor.
+    Published { state: State },
+
+    /// The co-processor did not update its storage with the state.
+    PublishRejected { root: String, uuid: String },
+
+    /// Updating to the latest historical root failed repeatedly.
+    UpdateFailed { failures: u64, error: String },
+}
+
+/// Delivers notifications to a set of webhook targets.
+///
+/// Failed deliveries are retried with exponential backoff, then appended to the dead-letter log.
+#[derive(Debug, Clone)]
+pub struct Webhooks {
+    targets: Vec<String>,
+    secret: Option<String>,
+    retries: u32,
+    backoff: Duration,
+    threshold: u64,
+    dead_letter: Option<Arc<Mutex<PathBuf>>>,
+    client: reqwest::Client,
+}
+
+impl Default for Webhooks {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl Webhooks {
+    pub fn new(targets: Vec<String>) -> Self {
+        Self {
+            targets,
+            secret: None,
+            retries: 3,
+            backoff: Duration::from_secs(1),
+            threshold: WEBHOOK_FAILURE_THRESHOLD,
+            dead_letter: None,
+            client: reqwest::Client::builder()
+                .connect_timeout(CONNECT_TIMEOUT)
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Signs the bodies with the provided HMAC secret.
+    pub fn with_secret<S: Into<String>>(mut self, secret: S) -> Self {
+        self.secret = Some(secret.into());
+        self
+    }
+
+    /// Retries a failed delivery `retries` times, doubling `backoff` after each attempt.
+    pub fn with_retries(mut self, retries: u32, backoff: Duration) -> Self {
+        self.retries = retries;
+        self.backoff = backoff;
+        self
+    }
+
+    /// Notifies after every `threshold` consecutive update failures.
+    pub fn with_failure_threshold(mut self, threshold: u64) -> Self {
+        self.threshold = threshold.max(1);
+        self
+    }
+
+    /// Appends undelivered notifications to the provided JSON lines file.
+    pub fn with_dead_letter<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.dead_letter = Some(Arc::new(Mutex::new(path.into())));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.targets.is_empty()
+    }
+
+    /// Records a failed update, notifying if the consecutive `failures`, as counted by the
+    /// service health, reached a multiple of the threshold.
+    pub fn update_failed(&self, failures: u64, error: &anyhow::Error) {
+        if failures > 0 && failures % self.threshold == 0 {
+            self.notify(Notification::UpdateFailed {
+                failures,
+                error: error.to_string(),
+            });
+        }
+    }
+
+    /// Delivers the notification in the background.
+    pub fn notify(&self, notification: Notification) {
+        if self.is_empty() {
+            return;
+        }
+
+        let webhooks = self.clone();
+
+        tokio::spawn(async move { webhooks.send(&notification).await });
+    }
+
+    /// Delivers the notification to all the targets, returning the number of failed targets.
+    pub async fn send(&self, notification: &Notification) -> usize {
+        let body = match serde_json::to_vec(notification) {
+            Ok(b) => b,
+            Err(e) => {
+                tracing::error!("failed to serialize notification: {e}");
+                return self.targets.len();
+            }
+        };
+
+        let mut failed = 0;
+
+        for url in &self.targets {
+            if let Err(e) = self.deliver(url, &body).await {
+                tracing::error!("webhook `{url}` failed: {e}");
+
+                self.dead_letter(url, &body, &e).await;
+                failed += 1;
+            }
+        }
+
+        failed
+    }
+
+    async fn deliver(&self, url: &str, body: &[u8]) -> anyhow::Result<()> {
+        let mut backoff = self.backoff;
+        let mut attempt = 0;
+
+        loop {
+            let mut request = self
+                .client
+                .post(url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(body.to_vec());
+
+            if let Some(secret) = &self.secret {
+                request = request.header(SIGNATURE_HEADER, Self::sign(secret, body)?);
+            }
+
+            let error = match request.send().await {
+                Ok(r) if r.status().is_success() => return Ok(()),
+                Ok(r) => anyhow::anyhow!("unexpected status `{}`", r.status()),
+                Err(e) => e.into(),
+            };
+
+            if attempt >= self.retries {
+                return Err(error);
+            }
+
+            tracing::warn!("webhook `{url}` failed: {error}; retrying in {backoff:?}...");
+
+            tokio::time::sleep(backoff).await;
+
+            attempt += 1;
+            backoff *= 2;
+        }
+    }
+
+    /// Computes the signature header value of the body.
+    pub fn sign(secret: &str, body: &[u8]) -> anyhow::Result<String> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+            .map_err(|e| anyhow::anyhow!("invalid secret: {e}"))?;
+
+        mac.update(body);
+
+        Ok(format!(
+            "sha256={}",
+            hex::encode(mac.finalize().into_bytes())
+        ))
+    }
+
+    async fn dead_letter(&self, url: &str, body: &[u8], error: &anyhow::Error) {
+        let path = match &self.dead_letter {
+            Some(p) => p,
+            None => return,
+        };
+
+        let record = json!({
+            "url": url,
+            "error": error.to_string(),
+            "body": String::from_utf8_lossy(body),
+        });
+
+        let path = path.lock().await;
+        let written = async {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).await?;
+            }
+
+            let mut file = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&*path)
+                .await?;
+
+            file.write_all(format!("{record}\n").as_bytes()).await?;
+            file.flush().await
+        };
+
+        if let Err(e) = written.await {
+            tracing::error!("failed to write dead letter: {e}");
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use valence_coprocessor_domain_prover::EvictionPolicy;
+use valence_coprocessor_domain_prover_service::Config;
+
+fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+    vars.iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+#[test]
+fn missing_keys_take_defaults() {
+    let config = Config::from_toml("capacity = 10", vars(&[])).unwrap();
+
+    assert_eq!(config.capacity, 10);
+    assert_eq!(config.interval, Config::default().interval);
+    assert!(config.validate().is_ok());
+}
+
+#[test]
+fn env_overrides_the_file() {
+    let config = Config::from_toml(
+        "capacity = 10\nstrict = false",
+        vars(&[
+            ("DOMAIN_PROVER_CAPACITY", "20"),
+            ("DOMAIN_PROVER_STRICT", "true"),
+            ("DOMAIN_PROVER_COPROCESSOR", "http://localhost:37281"),
+            ("DOMAIN_PROVER_WEBHOOKS", r#"["http://localhost:8080"]"#),
+            ("OTHER_CAPACITY", "30"),
+        ]),
+    )
+    .unwrap();
+
+    assert_eq!(config.capacity, 20);
+    assert!(config.strict);
+    assert_eq!(config.coprocessor, "http://localhost:37281");
+    assert_eq!(config.webhooks, vec!["http://localhost:8080".to_string()]);
+}
+
+#[test]
+fn string_keys_are_taken_verbatim() {
+    let config = Config::from_toml(
+        "",
+        vars(&[
+            ("DOMAIN_PROVER_ADMIN_TOKEN", "1234"),
+            ("DOMAIN_PROVER_WEBHOOK_SECRET", "true"),
+            ("DOMAIN_PROVER_STORAGE", "[states]"),
+        ]),
+    )
+    .unwrap();
+
+    assert_eq!(config.admin_token.as_deref(), Some("1234"));
+    assert_eq!(config.webhook_secret.as_deref(), Some("true"));
+    assert_eq!(config.storage, Some("[states]".into()));
+}
+
+#[test]
+fn unknown_env_keys_are_ignored() {
+    let config = Config::from_toml(
+        "capacity = 10",
+        vars(&[
+            ("DOMAIN_PROVER_IMAGE", "latest"),
+            ("DOMAIN_PROVER_CAPACITY", "20"),
+        ]),
+    )
+    .unwrap();
+
+    assert_eq!(config.capacity, 20);
+}
+
+#[test]
+fn keys_cover_the_configuration() {
+    let config = Config {
+        domains: Some("domains.json".into()),
+        max_updates: Some(100),
+        webhook_secret: Some("secret".into()),
+        webhook_dead_letter: Some("dead.jsonl".into()),
+        storage: Some("states.bin".into()),
+        admin_token: Some("token".into()),
+        ..Default::default()
+    };
+
+    let table: toml::Table = config.to_toml().unwrap().parse().unwrap();
+    let mut keys: Vec<_> = table.keys().map(String::as_str).collect();
+    let mut expected = Config::KEYS.to_vec();
+
+    keys.sort();
+    expected.sort();
+
+    assert_eq!(keys, expected);
+}
+
+#[test]
+fn eviction_policies_are_parsed() {
+    let config = Config::from_toml("eviction = { ttl = 3600 }", vars(&[])).unwrap();
+
+    assert_eq!(config.eviction, EvictionPolicy::Ttl(3600));
+
+    let config = Config::from_toml(
+        "eviction = \"latest\"",
+        vars(&[("DOMAIN_PROVER_EVICTION", "logarithmic")]),
+    )
+    .unwrap();
+
+    assert_eq!(config.eviction, EvictionPolicy::Logarithmic);
+    assert!(Config::from_toml("eviction = \"oldest\"", vars(&[])).is_err());
+}
+
+#[test]
+fn invalid_configurations_are_rejected() {
+    assert!(Config::from_toml("unknown = 1", vars(&[])).is_err());
+    assert!(Config::from_toml("capacity = \"ten\"", vars(&[])).is_err());
+
+    let config = Config::from_toml("max_wrappers = 0", vars(&[])).unwrap();
+    let error = config.validate().unwrap_err().to_string();
+
+    assert!(error.contains("max_wrappers"));
+
+    let config = Config::from_toml("webhook_failure_threshold = 0", vars(&[])).unwrap();
+    let error = config.validate().unwrap_err().to_string();
+
+    assert!(error.contains("webhook_failure_threshold"));
+
+    let config = Config::from_toml("webhooks = [\"ftp://localhost\"]", vars(&[])).unwrap();
+
+    assert!(config.validate().is_err());
+}
+
+#[test]
+fn printed_config_roundtrips_without_secrets() {
+    let config = Config {
+        webhooks: vec!["https://localhost".into()],
+        webhook_secret: Some("secret".into()),
+        max_updates: Some(100),
+        ..Default::default()
+    };
+
+    let printed = config.to_toml().unwrap();
+    let parsed = Config::from_toml(&printed, vars(&[])).unwrap();
+
+    assert!(!printed.contains("\"secret\""));
+    assert_eq!(parsed.max_updates, Some(100));
+    assert_eq!(parsed.bind, config.bind);
+}
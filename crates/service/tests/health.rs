@@ -0,0 +1,53 @@
+use std::time::Duration;
+
+use valence_coprocessor_domain_prover_service::{App, FailureClass, HealthStatus};
+
+fn failure(class: FailureClass) -> anyhow::Result<()> {
+    Err(anyhow::anyhow!("boom").context(class))
+}
+
+#[test]
+fn failures_are_classified() {
+    let e = failure(FailureClass::Prover).unwrap_err();
+
+    assert_eq!(FailureClass::of(&e), FailureClass::Prover);
+    assert_eq!(format!("{e:#}"), "prover failure: boom");
+    assert_eq!(
+        FailureClass::of(&anyhow::anyhow!("boom")),
+        FailureClass::Unknown
+    );
+}
+
+#[test]
+fn backoff_grows_up_to_the_class_cap() {
+    let class = FailureClass::Network;
+
+    assert_eq!(class.backoff(1), Duration::from_secs(5));
+    assert_eq!(class.backoff(2), Duration::from_secs(10));
+    assert_eq!(class.backoff(3), Duration::from_secs(20));
+    assert_eq!(class.backoff(100), Duration::from_secs(300));
+    assert!(FailureClass::InvalidUpdate.backoff(1) > class.backoff(1));
+}
+
+#[tokio::test]
+async fn consecutive_failures_degrade_health() {
+    let app = App::new(10).with_failure_threshold(2);
+    let interval = Duration::from_secs(1);
+
+    let delay = app.record_update(&failure(FailureClass::Network), interval);
+
+    assert_eq!(delay, Duration::from_secs(5));
+    assert_eq!(app.health().status, HealthStatus::Healthy);
+
+    let delay = app.record_update(&failure(FailureClass::Prover), interval);
+    let health = app.health();
+
+    assert_eq!(delay, Duration::from_secs(60));
+    assert_eq!(health.status, HealthStatus::Degraded);
+    assert_eq!(health.failures, 2);
+    assert_eq!(health.class, Some(FailureClass::Prover));
+
+    assert_eq!(app.record_update(&Ok(()), interval), interval);
+    assert_eq!(app.health().status, HealthStatus::Healthy);
+    assert_eq!(app.health().failures, 0);
+}
@@ -0,0 +1,126 @@
+use valence_coprocessor::{HistoricalTransitionProof, HistoricalUpdate};
+use valence_coprocessor_domain_prover_service::{FileStorage, HistoryCache, HistoryEntry, Storage};
+
+/// Returns the transitions of a chain of `n` roots, starting from `[0; 32]`.
+fn chain(n: u8) -> Vec<HistoricalTransitionProof> {
+    (1..=n)
+        .map(|i| HistoricalTransitionProof {
+            update: HistoricalUpdate {
+                uuid: [i; 16],
+                previous: [i - 1; 32],
+                root: [i; 32],
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .collect()
+}
+
+#[test]
+fn ranges_hit_only_if_complete() {
+    let cache = HistoryCache::new(10);
+    let transitions = chain(4);
+
+    assert!(cache.transitions(&[0; 32], &[4; 32]).is_none());
+
+    cache.insert_transitions(&transitions);
+
+    assert_eq!(cache.transitions(&[0; 32], &[4; 32]).unwrap(), transitions);
+    assert_eq!(
+        cache.transitions(&[1; 32], &[3; 32]).unwrap(),
+        transitions[1..3]
+    );
+    assert!(cache.transitions(&[0; 32], &[5; 32]).is_none());
+    assert!(cache.transitions(&[4; 32], &[1; 32]).is_none());
+
+    // transitions answer the update lookups
+    assert_eq!(cache.update(&[2; 32]), Some(transitions[1].update.clone()));
+
+    let stats = cache.stats();
+
+    assert_eq!(stats.entries, 4);
+    assert_eq!(stats.hits, 3);
+    assert_eq!(stats.misses, 3);
+    assert_eq!(stats.hit_rate, 0.5);
+}
+
+#[test]
+fn least_recently_used_entries_are_evicted() {
+    let cache = HistoryCache::new(3);
+    let transitions = chain(4);
+
+    cache.insert_transitions(&transitions[..3]);
+
+    // touching the first entry makes the second the least recently used
+    assert!(cache.update(&[1; 32]).is_some());
+
+    cache.insert_transitions(&transitions[3..]);
+
+    assert!(cache.update(&[2; 32]).is_none());
+    assert!(cache.update(&[1; 32]).is_some());
+    assert!(cache.update(&[4; 32]).is_some());
+
+    // an update doesn't override a cached transition
+    cache.insert_update(transitions[3].update.clone());
+
+    assert_eq!(cache.entries().len(), 3);
+    assert!(cache
+        .entries()
+        .iter()
+        .all(|e| matches!(e, HistoryEntry::Transition(_))));
+}
+
+#[test]
+fn partial_ranges_keep_the_recency() {
+    let cache = HistoryCache::new(3);
+    let transitions = chain(4);
+
+    cache.insert_transitions(&transitions[..3]);
+
+    // the miss walks the cached chain without touching it
+    assert!(cache.transitions(&[9; 32], &[3; 32]).is_none());
+
+    cache.insert_transitions(&transitions[3..]);
+
+    assert!(cache.update(&[1; 32]).is_none());
+    assert!(cache.update(&[3; 32]).is_some());
+}
+
+#[test]
+fn zero_capacity_disables_the_cache() {
+    let cache = HistoryCache::new(0);
+
+    cache.insert_transitions(&chain(2));
+
+    assert!(cache.update(&[1; 32]).is_none());
+    assert_eq!(cache.stats().entries, 0);
+    assert_eq!(cache.stats().misses, 0);
+}
+
+#[test]
+fn entries_are_persisted_alongside_the_states() {
+    let path = std::env::temp_dir().join(format!("history-{}.bin", std::process::id()));
+    let storage = FileStorage::open(&path).unwrap();
+
+    assert!(storage.load_history().unwrap().is_empty());
+
+    let cache = HistoryCache::new(10);
+
+    cache.insert_transitions(&chain(2));
+    cache.insert_update(HistoricalUpdate {
+        root: [9; 32],
+        ..Default::default()
+    });
+
+    storage.flush_history(&cache.entries()).unwrap();
+
+    let restored = HistoryCache::new(10);
+
+    restored.extend(storage.load_history().unwrap());
+
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(path.with_extension("history")).unwrap();
+
+    assert_eq!(restored.entries(), cache.entries());
+    assert!(restored.transitions(&[0; 32], &[2; 32]).is_some());
+}
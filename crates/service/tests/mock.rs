@@ -0,0 +1,1178 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use msgpacker::Packable as _;
+use valence_coprocessor::{Hash, Proof};
+use valence_coprocessor_domain_prover::{
+    Circuit, CircuitOutput, CompressedPayload, Domain, EvictionPolicy, ProofSystem, State,
+    UpdateFailure, WrapperOutput,
+};
+use valence_coprocessor_domain_prover_service::{
+    mock::{MockCoprocessor, MockProver, MockVerifier},
+    App, DeferredProof, FailureClass, FileStorage, HistoryCache, HistoryEntry, JobStatus, Jobs,
+    Prover, Publication, Storage, JOBS_CAPACITY,
+};
+use valence_coprocessor_prover::types::ProofType;
+use valence_coprocessor_sp1::Sp1Hasher;
+
+/// The configuration of a mock app; the defaults are the ones of [app].
+struct Options {
+    capacity: usize,
+    domains: Vec<Domain>,
+    strict: bool,
+    preflight: bool,
+    max_updates: usize,
+    storage: Option<Box<dyn Storage>>,
+    history: Option<HistoryCache>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            capacity: 10,
+            domains: Circuit::default().domains,
+            strict: false,
+            preflight: false,
+            max_updates: usize::MAX,
+            storage: None,
+            history: None,
+        }
+    }
+}
+
+/// A boxed storage, so the options can hold any backend.
+struct Boxed(Box<dyn Storage>);
+
+impl Storage for Boxed {
+    fn load(&self) -> anyhow::Result<Vec<State>> {
+        self.0.load()
+    }
+
+    fn persist(&self, state: &State) -> anyhow::Result<()> {
+        self.0.persist(state)
+    }
+
+    fn flush(&self, states: &[State]) -> anyhow::Result<()> {
+        self.0.flush(states)
+    }
+
+    fn should_compact(&self) -> bool {
+        self.0.should_compact()
+    }
+
+    fn load_history(&self) -> anyhow::Result<Vec<HistoryEntry>> {
+        self.0.load_history()
+    }
+
+    fn flush_history(&self, entries: &[HistoryEntry]) -> anyhow::Result<()> {
+        self.0.flush_history(entries)
+    }
+}
+
+/// Initializes an app on top of the mock backends.
+///
+/// The mock blocks carry no light-client proofs, so the preflight is disabled unless requested.
+async fn app_with<P: Prover + Clone + 'static>(
+    coprocessor: &MockCoprocessor,
+    prover: &P,
+    options: Options,
+) -> anyhow::Result<App> {
+    let mut app = App::new(options.capacity)
+        .with_coprocessor_backend(coprocessor.clone())
+        .with_prover_backend(prover.clone())
+        .with_verifier_backend(MockVerifier)
+        .with_domains(options.domains)
+        .with_strict(options.strict)
+        .with_preflight(options.preflight)
+        .with_max_updates(options.max_updates);
+
+    if let Some(storage) = options.storage {
+        app = app.with_storage(Boxed(storage));
+    }
+
+    if let Some(history) = options.history {
+        app = app.with_history(history);
+    }
+
+    app.init().await
+}
+
+async fn app<P: Prover + Clone + 'static>(
+    capacity: usize,
+    coprocessor: &MockCoprocessor,
+    prover: &P,
+) -> App {
+    let options = Options {
+        capacity,
+        ..Default::default()
+    };
+
+    app_with(coprocessor, prover, options).await.unwrap()
+}
+
+#[tokio::test]
+async fn init_bootstraps_and_publishes() {
+    let coprocessor = MockCoprocessor::default();
+    let prover = MockProver::default();
+    let app = app(10, &coprocessor, &prover).await;
+
+    let latest = app.latest().await.unwrap();
+
+    assert_eq!(latest.update.root, Circuit::INITIAL_ROOT);
+    assert_eq!(latest.root().unwrap(), Circuit::INITIAL_ROOT);
+    assert_eq!(
+        latest.domains().unwrap(),
+        Circuit::domains_commitment::<Sp1Hasher>(app.domains())
+    );
+    assert_eq!(prover.calls(), 2);
+
+    let published = coprocessor.storage(app.id()).unwrap();
+    let published: State = serde_json::from_slice(&published).unwrap();
+
+    assert_eq!(published, latest);
+}
+
+#[tokio::test]
+async fn init_resumes_from_coprocessor() {
+    let coprocessor = MockCoprocessor::default();
+    let prover = MockProver::default();
+
+    app(10, &coprocessor, &prover).await;
+
+    let calls = prover.calls();
+    let app = app(10, &coprocessor, &prover).await;
+
+    assert_eq!(prover.calls(), calls);
+    assert_eq!(
+        app.latest().await.unwrap().update.root,
+        Circuit::INITIAL_ROOT
+    );
+}
+
+#[tokio::test]
+async fn changed_domains_are_bootstrapped() {
+    let coprocessor = MockCoprocessor::default();
+    let prover = MockProver::default();
+    app(10, &coprocessor, &prover).await;
+
+    let calls = prover.calls();
+
+    // the chain of the default domains can't be extended with another set
+    let app = app_with(
+        &coprocessor,
+        &prover,
+        Options {
+            domains: Vec::new(),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    let latest = app.latest().await.unwrap();
+    let domains = Circuit::domains_commitment::<Sp1Hasher>(&[]);
+
+    assert_eq!(prover.calls(), calls + 2);
+    assert_eq!(latest.root().unwrap(), Circuit::INITIAL_ROOT);
+    assert_eq!(latest.domains().unwrap(), domains);
+
+    let published: State = serde_json::from_slice(&coprocessor.storage(app.id()).unwrap()).unwrap();
+
+    assert_eq!(published, latest);
+
+    coprocessor.push([0xee; 32], 1);
+
+    let state = app.update_to_latest().await.unwrap().unwrap();
+
+    assert_eq!(state.domains().unwrap(), domains);
+}
+
+#[tokio::test]
+async fn strict_is_chained() {
+    let coprocessor = MockCoprocessor::default();
+    let prover = MockProver::default();
+    let strict = || Options {
+        strict: true,
+        ..Default::default()
+    };
+
+    app(10, &coprocessor, &prover).await;
+
+    // a non-strict chain is bootstrapped again in strict mode
+    let app = app_with(&coprocessor, &prover, strict()).await.unwrap();
+    let domain = app.domains()[0].id;
+
+    assert!(app.latest().await.unwrap().output().unwrap().strict);
+
+    coprocessor.push(domain, 1);
+
+    let state = app.update_to_latest().await.unwrap().unwrap();
+
+    assert!(state.output().unwrap().strict);
+
+    // and the strict chain is resumed
+    let calls = prover.calls();
+    let app = app_with(&coprocessor, &prover, strict()).await.unwrap();
+
+    assert_eq!(prover.calls(), calls);
+    assert_eq!(app.latest().await.unwrap().update.root, state.update.root);
+}
+
+#[tokio::test]
+async fn update_to_latest_proves_new_roots() {
+    let coprocessor = MockCoprocessor::default();
+    let prover = MockProver::default();
+    let app = app(10, &coprocessor, &prover).await;
+
+    assert!(app.update_to_latest().await.unwrap().is_none());
+
+    let domain = Circuit::default().domains[0].id;
+
+    coprocessor.push(domain, 1);
+    coprocessor.push([0xee; 32], 1);
+
+    let root = coprocessor.push(domain, 2);
+    let state = app.update_to_latest().await.unwrap().unwrap();
+
+    assert_eq!(state.update.root, root);
+    assert_eq!(state.root().unwrap(), root);
+    assert_eq!(state.output().unwrap().blocks[0].number, 2);
+    assert_eq!(app.latest().await.unwrap().update.root, state.update.root);
+    assert!(app.update_to_latest().await.unwrap().is_none());
+
+    let stats = &state.metadata.domains;
+
+    assert_eq!(stats.iter().map(|s| s.verified).sum::<u64>(), 2);
+    assert_eq!(stats.iter().map(|s| s.skipped).sum::<u64>(), 1);
+
+    let published = coprocessor.storage(app.id()).unwrap();
+    let published: State = serde_json::from_slice(&published).unwrap();
+
+    assert_eq!(published, state);
+}
+
+#[tokio::test]
+async fn advance_overlaps_pending_wrappers() {
+    let coprocessor = MockCoprocessor::default();
+    let prover = MockProver::default();
+    let app = app(10, &coprocessor, &prover).await;
+
+    assert!(app.advance().await.unwrap().is_none());
+
+    let root = coprocessor.push([0xee; 32], 1);
+    let wrapper = app.advance().await.unwrap().unwrap();
+
+    // the pending inner proof covers the head
+    assert!(app.advance().await.unwrap().is_none());
+
+    let state = wrapper.await.unwrap().unwrap();
+
+    assert_eq!(state.update.root, root);
+    assert_eq!(app.latest().await.unwrap().update.root, state.update.root);
+    assert!(app.advance().await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn init_restores_the_published_watermark() {
+    let path = std::env::temp_dir().join(format!("watermark-{}.bin", std::process::id()));
+    let coprocessor = MockCoprocessor::default();
+    let prover = MockProver::default();
+    let options = || Options {
+        storage: Some(Box::new(FileStorage::open(&path).unwrap())),
+        ..Default::default()
+    };
+
+    let previous = app_with(&coprocessor, &prover, options()).await.unwrap();
+
+    coprocessor.push([0xee; 32], 1);
+    previous.update_to_latest().await.unwrap().unwrap();
+
+    let root = coprocessor.push([0xee; 32], 2);
+    let latest = previous.update_to_latest().await.unwrap().unwrap();
+
+    let app = app_with(&coprocessor, &prover, options()).await.unwrap();
+    let mut events = app.subscribe();
+
+    // re-proving the published head doesn't publish it again
+    app.evict(&latest.update.uuid).await.unwrap().unwrap();
+
+    let id = app.prove(root).await.unwrap();
+
+    assert!(matches!(wait_job(&app, id).await, JobStatus::Done { .. }));
+
+    let event = events.recv().await.unwrap();
+
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(event.latest);
+    assert!(!event.published);
+}
+
+#[tokio::test]
+async fn states_are_evicted_oldest_first() {
+    let coprocessor = MockCoprocessor::default();
+    let prover = MockProver::default();
+    let app = app(2, &coprocessor, &prover).await;
+
+    let mut roots = vec![Circuit::INITIAL_ROOT];
+
+    for i in 0..3 {
+        roots.push(coprocessor.push([0xee; 32], i));
+        app.update_to_latest().await.unwrap().unwrap();
+    }
+
+    assert!(app.state(&roots[0]).await.is_none());
+    assert!(app.state(&roots[1]).await.is_none());
+    assert!(app.state(&roots[2]).await.is_some());
+    assert!(app.state(&roots[3]).await.is_some());
+
+    // proving from the retained lower bound still works after eviction
+    let root = coprocessor.push([0xee; 32], 3);
+    let state = app.update_to_latest().await.unwrap().unwrap();
+
+    assert_eq!(state.update.root, root);
+    assert!(app.state(&roots[2]).await.is_none());
+}
+
+#[tokio::test]
+async fn long_ranges_are_proven_in_chunks() {
+    let coprocessor = MockCoprocessor::default();
+    let prover = MockProver::default();
+    let app = app_with(
+        &coprocessor,
+        &prover,
+        Options {
+            max_updates: 2,
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    let calls = prover.calls();
+    let mut events = app.subscribe();
+    let roots: Vec<_> = (0..5).map(|i| coprocessor.push([0xee; 32], i)).collect();
+    let state = app.update_to_latest().await.unwrap().unwrap();
+
+    assert_eq!(state.update.root, roots[4]);
+    assert_eq!(state.root().unwrap(), roots[4]);
+
+    // only the final state is published
+    let published: Vec<_> = (0..3)
+        .map(|_| events.try_recv().unwrap())
+        .map(|e| (e.state.update.root, e.published))
+        .collect();
+
+    assert_eq!(
+        published,
+        vec![(roots[1], false), (roots[3], false), (roots[4], true)]
+    );
+
+    let stored: State = serde_json::from_slice(&coprocessor.storage(app.id()).unwrap()).unwrap();
+
+    assert_eq!(stored, state);
+
+    // three inner proofs, two intermediate wrappers and the final wrapper
+    assert_eq!(prover.calls() - calls, 6);
+
+    let intermediate = app.state(&roots[1]).await.unwrap();
+
+    assert_eq!(intermediate.root().unwrap(), roots[1]);
+    assert_eq!(
+        app.state(&roots[3]).await.unwrap().root().unwrap(),
+        roots[3]
+    );
+    assert!(app.state(&roots[0]).await.is_none());
+    assert!(app.state(&roots[2]).await.is_none());
+
+    let skipped = intermediate.metadata.domains.iter().map(|s| s.skipped);
+
+    assert_eq!(skipped.sum::<u64>(), 2);
+}
+
+/// A prover that holds the wrapper proofs until `open` is set, counting the inner proofs.
+#[derive(Default, Clone)]
+struct GatedProver {
+    prover: MockProver,
+    open: Arc<AtomicBool>,
+    inner: Arc<AtomicUsize>,
+}
+
+impl Prover for GatedProver {
+    fn get_sp1_proof(
+        &self,
+        circuit: Hash,
+        proof_type: ProofType,
+        input: &[u8],
+        recursive: &[DeferredProof],
+        elf: &'static [u8],
+    ) -> anyhow::Result<Proof> {
+        if matches!(proof_type, ProofType::Groth16) {
+            while !self.open.load(Ordering::SeqCst) {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        } else {
+            self.inner.fetch_add(1, Ordering::SeqCst);
+        }
+
+        self.prover
+            .get_sp1_proof(circuit, proof_type, input, recursive, elf)
+    }
+}
+
+#[tokio::test]
+async fn chunks_are_proven_while_wrapping() {
+    let coprocessor = MockCoprocessor::default();
+    let prover = GatedProver {
+        open: Arc::new(AtomicBool::new(true)),
+        ..Default::default()
+    };
+    let app = app_with(
+        &coprocessor,
+        &prover,
+        Options {
+            max_updates: 1,
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    prover.open.store(false, Ordering::SeqCst);
+
+    let inner = prover.inner.load(Ordering::SeqCst);
+    let roots: Vec<_> = (0..3).map(|i| coprocessor.push([0xee; 32], i)).collect();
+    let update = tokio::spawn({
+        let app = app.clone();
+        async move { app.update_to_latest().await }
+    });
+
+    // every chunk is proven while the first intermediate wrapper is held
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while prover.inner.load(Ordering::SeqCst) - inner < 3 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+
+    assert!(app.state(&roots[0]).await.is_none());
+
+    prover.open.store(true, Ordering::SeqCst);
+
+    let state = update.await.unwrap().unwrap().unwrap();
+
+    assert_eq!(state.update.root, roots[2]);
+    assert!(app.state(&roots[0]).await.is_some());
+    assert!(app.state(&roots[1]).await.is_some());
+}
+
+/// A prover that commits a forged root in its wrapper proofs once `faulty` is set, and in its
+/// inner proofs once `faulty_inner` is set. Fails its wrapper proofs once `failing` is set.
+#[derive(Default, Clone)]
+struct FaultyProver {
+    prover: MockProver,
+    faulty: Arc<AtomicBool>,
+    faulty_inner: Arc<AtomicBool>,
+    failing: Arc<AtomicBool>,
+}
+
+impl Prover for FaultyProver {
+    fn get_sp1_proof(
+        &self,
+        circuit: Hash,
+        proof_type: ProofType,
+        input: &[u8],
+        recursive: &[DeferredProof],
+        elf: &'static [u8],
+    ) -> anyhow::Result<Proof> {
+        let proof = self
+            .prover
+            .get_sp1_proof(circuit, proof_type, input, recursive, elf)?;
+
+        if !matches!(proof_type, ProofType::Groth16) {
+            if !self.faulty_inner.load(Ordering::SeqCst) {
+                return Ok(proof);
+            }
+
+            let mut output = CircuitOutput::decode(&proof.decode()?.1)?;
+
+            output.root = [0xaa; 32];
+
+            return Ok(Proof::new(Vec::new(), output.encode()));
+        }
+
+        anyhow::ensure!(
+            !self.failing.load(Ordering::SeqCst),
+            "wrapper prover offline"
+        );
+
+        if !self.faulty.load(Ordering::SeqCst) {
+            return Ok(proof);
+        }
+
+        let mut output = WrapperOutput::decode(&proof.decode()?.1)?;
+
+        output.root = [0xaa; 32];
+
+        Ok(Proof::new(Vec::new(), output.encode()))
+    }
+}
+
+#[tokio::test]
+async fn mismatched_inner_proofs_are_rejected() {
+    let coprocessor = MockCoprocessor::default();
+    let prover = FaultyProver::default();
+    let app = app(10, &coprocessor, &prover).await;
+
+    let published = coprocessor.storage(app.id()).unwrap();
+
+    prover.faulty_inner.store(true, Ordering::SeqCst);
+
+    let root = coprocessor.push([0xee; 32], 1);
+    let calls = prover.prover.calls();
+    let error = app.update_to_latest().await.unwrap_err();
+
+    assert_eq!(FailureClass::of(&error), FailureClass::InvalidUpdate);
+    assert!(format!("{error:#}").contains("inner proof root mismatch"));
+
+    // no wrapper is computed for a rejected inner proof
+    assert_eq!(prover.prover.calls() - calls, 1);
+    assert!(app.state(&root).await.is_none());
+    assert_eq!(coprocessor.storage(app.id()).unwrap(), published);
+}
+
+#[tokio::test]
+async fn mismatched_wrappers_are_rejected() {
+    let coprocessor = MockCoprocessor::default();
+    let prover = FaultyProver::default();
+    let app = app(10, &coprocessor, &prover).await;
+
+    let published = coprocessor.storage(app.id()).unwrap();
+
+    prover.faulty.store(true, Ordering::SeqCst);
+
+    let root = coprocessor.push([0xee; 32], 1);
+
+    assert!(app.update_to_latest().await.is_err());
+    assert!(app.state(&root).await.is_none());
+    assert!(app.state(&[0xaa; 32]).await.is_none());
+    assert_eq!(coprocessor.storage(app.id()).unwrap(), published);
+}
+
+#[tokio::test]
+async fn failing_wrappers_are_recorded() {
+    let coprocessor = MockCoprocessor::default();
+    let prover = FaultyProver::default();
+    let app = app(10, &coprocessor, &prover).await;
+
+    let interval = Duration::from_millis(10);
+
+    assert_eq!(app.background_update(interval).await, interval);
+
+    let last_success = app.health().last_success;
+
+    prover.faulty.store(true, Ordering::SeqCst);
+    coprocessor.push([0xee; 32], 1);
+
+    assert_eq!(app.background_update(interval).await, interval);
+
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while app.health().failures == 0 {
+            tokio::time::sleep(interval).await;
+        }
+    })
+    .await
+    .unwrap();
+
+    let health = app.health();
+
+    assert_eq!(health.failures, 1);
+    assert_eq!(health.class, Some(FailureClass::InvalidUpdate));
+    assert!(health.error.is_some());
+    assert_eq!(health.last_success, last_success);
+}
+
+#[tokio::test]
+async fn failing_wrappers_back_off() {
+    let coprocessor = MockCoprocessor::default();
+    let prover = FaultyProver::default();
+    let app = app(10, &coprocessor, &prover).await;
+
+    let interval = Duration::from_millis(10);
+
+    prover.failing.store(true, Ordering::SeqCst);
+    coprocessor.push([0xee; 32], 1);
+
+    assert_eq!(app.background_update(interval).await, interval);
+
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while app.health().failures == 0 {
+            tokio::time::sleep(interval).await;
+        }
+    })
+    .await
+    .unwrap();
+
+    let calls = prover.prover.calls();
+    let backoff = FailureClass::Wrapper.backoff(1);
+
+    coprocessor.push([0xee; 32], 2);
+
+    assert_eq!(app.background_update(interval).await, backoff);
+    assert_eq!(prover.prover.calls(), calls);
+    assert_eq!(app.health().class, Some(FailureClass::Wrapper));
+    assert_eq!(app.health().retry_in, backoff.as_secs());
+
+    prover.failing.store(false, Ordering::SeqCst);
+
+    assert_eq!(app.background_update(interval).await, interval);
+    assert!(prover.prover.calls() > calls);
+}
+
+#[tokio::test]
+async fn eviction_applies_to_shared_states() {
+    let coprocessor = MockCoprocessor::default();
+    let prover = MockProver::default();
+    let shared = app(3, &coprocessor, &prover).await;
+    let app = shared.clone().with_eviction(EvictionPolicy::Logarithmic);
+    let first = shared.latest().await.unwrap();
+
+    for i in 0..4 {
+        coprocessor.push([0xee; 32], i);
+        app.update_to_latest().await.unwrap().unwrap();
+    }
+
+    assert_eq!(shared.states().await.len(), 3);
+    assert!(shared.state_by_uuid(&first.update.uuid).await.is_some());
+    assert_eq!(shared.latest().await.unwrap().metadata.height, 4);
+}
+
+#[tokio::test]
+async fn inserted_states_are_broadcast() {
+    let coprocessor = MockCoprocessor::default();
+    let prover = MockProver::default();
+    let app = app(10, &coprocessor, &prover).await;
+    let mut events = app.subscribe();
+
+    let root = coprocessor.push([0xee; 32], 1);
+    let state = app.update_to_latest().await.unwrap().unwrap();
+    let event = events.try_recv().unwrap();
+
+    assert_eq!(event.state, state);
+    assert_eq!(event.state.update.root, root);
+    assert!(event.latest);
+    assert!(event.published);
+    assert!(events.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn historical_states_are_not_latest() {
+    let coprocessor = MockCoprocessor::default();
+    let prover = MockProver::default();
+    let app = app(10, &coprocessor, &prover).await;
+
+    let root = coprocessor.push([0xee; 32], 1);
+
+    coprocessor.push([0xee; 32], 2);
+
+    let latest = app.update_to_latest().await.unwrap().unwrap();
+    let mut events = app.subscribe();
+    let id = app.prove(root).await.unwrap();
+
+    assert!(matches!(wait_job(&app, id).await, JobStatus::Done { .. }));
+
+    let event = events.recv().await.unwrap();
+
+    assert_eq!(event.state.update.root, root);
+    assert!(!event.latest);
+    assert!(!event.published);
+    assert_eq!(app.latest().await.unwrap(), latest);
+}
+
+#[tokio::test]
+async fn readiness_reports_lag() {
+    let coprocessor = MockCoprocessor::default();
+    let prover = MockProver::default();
+    let app = app(10, &coprocessor, &prover).await;
+    let timeout = Duration::from_secs(1);
+
+    let readiness = app.readiness(timeout).await;
+
+    assert!(readiness.ready);
+    assert!(readiness.coprocessor);
+    assert_eq!(readiness.lag.updates, 0);
+    assert_eq!(readiness.latest, readiness.head);
+
+    coprocessor.push([0xee; 32], 1);
+    coprocessor.push([0xee; 32], 2);
+
+    // the probes don't measure the lag; the updates do
+    let fetches = coprocessor.fetches();
+    let readiness = app.readiness(timeout).await;
+
+    assert!(readiness.ready);
+    assert_ne!(readiness.latest, readiness.head);
+    assert_eq!(readiness.lag.updates, 0);
+
+    app.readiness(timeout).await;
+
+    // only the head update is fetched, once
+    assert_eq!(coprocessor.fetches(), fetches + 1);
+
+    app.update_to_latest().await.unwrap().unwrap();
+
+    let readiness = app.readiness(timeout).await;
+
+    assert_eq!(readiness.lag.updates, 2);
+    assert!(readiness.lag.seconds <= 1);
+
+    assert!(app.update_to_latest().await.unwrap().is_none());
+    assert_eq!(app.readiness(timeout).await.lag.updates, 0);
+}
+
+/// A prover that takes a while to compute the wrapper proofs.
+#[derive(Default, Clone)]
+struct SlowProver {
+    prover: MockProver,
+}
+
+impl Prover for SlowProver {
+    fn get_sp1_proof(
+        &self,
+        circuit: Hash,
+        proof_type: ProofType,
+        input: &[u8],
+        recursive: &[DeferredProof],
+        elf: &'static [u8],
+    ) -> anyhow::Result<Proof> {
+        if matches!(proof_type, ProofType::Groth16) {
+            std::thread::sleep(Duration::from_millis(200));
+        }
+
+        self.prover
+            .get_sp1_proof(circuit, proof_type, input, recursive, elf)
+    }
+}
+
+#[tokio::test]
+async fn shutdown_stops_new_work() {
+    let path = std::env::temp_dir().join(format!("shutdown-{}.bin", std::process::id()));
+    let coprocessor = MockCoprocessor::default();
+    let prover = SlowProver::default();
+    let app = app_with(
+        &coprocessor,
+        &prover,
+        Options {
+            storage: Some(Box::new(FileStorage::open(&path).unwrap())),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    let root = coprocessor.push([0xee; 32], 1);
+    let wrapper = app.advance().await.unwrap().unwrap();
+
+    assert!(!wrapper.is_finished());
+
+    app.shutdown(Duration::from_secs(5)).await.unwrap();
+
+    // the in-flight wrapper is drained before the states are flushed
+    assert!(wrapper.is_finished());
+
+    let state = wrapper.await.unwrap().unwrap();
+    let stored = FileStorage::open(&path).unwrap().load().unwrap();
+
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(state.update.root, root);
+    assert_eq!(stored.last(), Some(&state));
+    assert_eq!(stored.len(), 2);
+
+    coprocessor.push([0xee; 32], 2);
+
+    assert!(app.is_closing());
+    assert!(app.prove(Circuit::INITIAL_ROOT).await.is_err());
+    assert!(app.advance().await.unwrap().is_none());
+}
+
+async fn wait_job(app: &App, id: u64) -> JobStatus {
+    loop {
+        match app.job(id).await.unwrap() {
+            s if s.is_finished() => return s,
+            _ => tokio::time::sleep(Duration::from_millis(10)).await,
+        }
+    }
+}
+
+#[tokio::test]
+async fn job_queue_is_bounded() {
+    let jobs = Jobs::default();
+
+    for _ in 0..JOBS_CAPACITY {
+        jobs.create().await.unwrap();
+    }
+
+    assert!(jobs.create().await.is_none());
+
+    let done = JobStatus::Done {
+        root: String::new(),
+        uuid: String::new(),
+    };
+
+    jobs.set(0, done).await;
+
+    assert_eq!(jobs.create().await, Some(JOBS_CAPACITY as u64));
+    assert!(jobs.get(0).await.is_none());
+}
+
+#[tokio::test]
+async fn admin_operations_update_the_cache() {
+    let coprocessor = MockCoprocessor::default();
+    let prover = MockProver::default();
+    let app = app(10, &coprocessor, &prover).await;
+
+    let mut uuids = vec![app.latest().await.unwrap().update.uuid];
+
+    for i in 0..3 {
+        coprocessor.push([0xee; 32], i);
+        uuids.push(app.update_to_latest().await.unwrap().unwrap().update.uuid);
+    }
+
+    assert_eq!(app.states().await.len(), 4);
+
+    // proving the cached head is a cache hit
+    let calls = prover.calls();
+    let id = app.prove_head().await.unwrap();
+
+    assert_eq!(
+        wait_job(&app, id).await,
+        JobStatus::Done {
+            root: hex::encode(app.latest().await.unwrap().update.root),
+            uuid: hex::encode(uuids[3]),
+        }
+    );
+    assert_eq!(prover.calls(), calls);
+
+    assert!(app.evict(&uuids[1]).await.unwrap().is_some());
+    assert!(app.evict(&uuids[1]).await.unwrap().is_none());
+    assert!(app.state_by_uuid(&uuids[1]).await.is_none());
+
+    assert_eq!(app.set_capacity(2).await.unwrap(), 1);
+    assert_eq!(app.capacity().await, 2);
+    assert!(app.state_by_uuid(&uuids[0]).await.is_none());
+
+    // re-proving a cached root computes a new proof from the previous state
+    let root = app.state_by_uuid(&uuids[3]).await.unwrap().update.root;
+    let calls = prover.calls();
+    let id = app.reprove(root).await.unwrap();
+
+    assert!(matches!(wait_job(&app, id).await, JobStatus::Done { .. }));
+    assert_eq!(prover.calls() - calls, 2);
+
+    // republishing an older state overrides the co-processor storage
+    assert_eq!(
+        app.republish(&uuids[2]).await.unwrap(),
+        Some(Publication::Published)
+    );
+    assert_eq!(app.republish(&uuids[0]).await.unwrap(), None);
+
+    let published = coprocessor.storage(app.id()).unwrap();
+    let published: State = serde_json::from_slice(&published).unwrap();
+
+    assert_eq!(published.update.uuid, uuids[2]);
+
+    // proving a new head runs as a job
+    let root = coprocessor.push([0xee; 32], 3);
+    let id = app.prove_head().await.unwrap();
+
+    assert!(matches!(wait_job(&app, id).await, JobStatus::Done { .. }));
+    assert!(app.state(&root).await.is_some());
+}
+
+#[tokio::test]
+async fn states_are_paginated() {
+    let coprocessor = MockCoprocessor::default();
+    let prover = MockProver::default();
+    let app = app(10, &coprocessor, &prover).await;
+
+    for i in 0..4 {
+        coprocessor.push([0xee; 32], i);
+        app.update_to_latest().await.unwrap().unwrap();
+    }
+
+    let mut uuids = Vec::new();
+    let mut after = None;
+
+    loop {
+        let (states, next) = app.states_page(after, 2).await;
+
+        assert!(states.len() <= 2);
+
+        uuids.extend(states.iter().map(|s| s.update.uuid));
+        after = next;
+
+        if after.is_none() {
+            break;
+        }
+
+        assert_eq!(after, uuids.last().copied());
+    }
+
+    let states: Vec<_> = app.states().await.iter().map(|s| s.update.uuid).collect();
+
+    assert_eq!(uuids, states);
+    assert_eq!(uuids.len(), 5);
+    assert_eq!(app.states_page(None, 0).await.0.len(), 1);
+    assert!(app.states_page(Some(uuids[4]), 2).await.0.is_empty());
+}
+
+#[tokio::test]
+async fn historical_fetches_are_cached() {
+    let coprocessor = MockCoprocessor::default();
+    let prover = FaultyProver::default();
+    let app = app(10, &coprocessor, &prover).await;
+
+    coprocessor.push([0xee; 32], 1);
+    coprocessor.push([0xee; 32], 2);
+
+    prover.faulty.store(true, Ordering::SeqCst);
+
+    assert!(app.update_to_latest().await.is_err());
+
+    // the retry reuses the head update and the transitions of the failed attempt
+    let fetches = coprocessor.fetches();
+    let stats = app.history();
+
+    prover.faulty.store(false, Ordering::SeqCst);
+
+    let state = app.update_to_latest().await.unwrap().unwrap();
+
+    assert_eq!(coprocessor.fetches(), fetches);
+    assert!(app.update_to_latest().await.unwrap().is_none());
+    assert_eq!(coprocessor.fetches(), fetches);
+
+    assert_eq!(app.history().misses, stats.misses);
+    assert!(app.history().hits > stats.hits);
+    assert_eq!(app.readiness(Duration::from_secs(1)).await.lag.updates, 0);
+    assert_eq!(app.latest().await.unwrap().update.root, state.update.root);
+
+    // the exported counter is the one of the cache
+    let hits = app.metrics().history.with_label_values(&["hit"]).get();
+
+    assert_eq!(hits, app.history().hits);
+}
+
+#[tokio::test]
+async fn persistent_history_is_flushed_with_the_states() {
+    let path = std::env::temp_dir().join(format!("flushed-history-{}.bin", std::process::id()));
+    let coprocessor = MockCoprocessor::default();
+    let prover = MockProver::default();
+    let app = app_with(
+        &coprocessor,
+        &prover,
+        Options {
+            storage: Some(Box::new(FileStorage::open(&path).unwrap())),
+            history: Some(HistoryCache::default().with_persistence(true)),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    let root = coprocessor.push([0xee; 32], 1);
+
+    app.update_to_latest().await.unwrap().unwrap();
+
+    // any flush of the states writes the history, not only the shutdown
+    app.set_capacity(10).await.unwrap();
+
+    let history = FileStorage::open(&path).unwrap().load_history().unwrap();
+
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(path.with_extension("history")).unwrap();
+
+    assert!(history.iter().any(|e| e.update().root == root));
+}
+
+/// A file storage with slow flushes, to widen the window between the snapshot and the rename.
+struct SlowStorage(FileStorage);
+
+impl Storage for SlowStorage {
+    fn load(&self) -> anyhow::Result<Vec<State>> {
+        self.0.load()
+    }
+
+    fn persist(&self, state: &State) -> anyhow::Result<()> {
+        self.0.persist(state)
+    }
+
+    fn flush(&self, states: &[State]) -> anyhow::Result<()> {
+        std::thread::sleep(Duration::from_millis(200));
+
+        self.0.flush(states)
+    }
+}
+
+#[tokio::test]
+async fn concurrent_appends_survive_flushes() {
+    let path = std::env::temp_dir().join(format!("concurrent-{}.bin", std::process::id()));
+    let coprocessor = MockCoprocessor::default();
+    let prover = MockProver::default();
+    let app = app_with(
+        &coprocessor,
+        &prover,
+        Options {
+            storage: Some(Box::new(SlowStorage(FileStorage::open(&path).unwrap()))),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    coprocessor.push([0xee; 32], 1);
+
+    // the state is inserted after the flush snapshot, but before its rename
+    let (flushed, state) = tokio::join!(app.set_capacity(10), async {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        app.update_to_latest().await
+    });
+
+    flushed.unwrap();
+
+    let state = state.unwrap().unwrap();
+    let stored = FileStorage::open(&path).unwrap().load().unwrap();
+
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(stored.last(), Some(&state));
+    assert_eq!(stored.len(), 2);
+}
+
+#[tokio::test]
+async fn rejected_updates_are_not_proven() {
+    let coprocessor = MockCoprocessor::default();
+    let prover = MockProver::default();
+    let app = app_with(
+        &coprocessor,
+        &prover,
+        Options {
+            preflight: true,
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    let calls = prover.calls();
+
+    // the mock block of an elected domain carries no light-client proof
+    let domain = app.domains()[0].id;
+
+    coprocessor.push(domain, 1);
+
+    let root = coprocessor.push([0xee; 32], 2);
+    let error = app.update_to_latest().await.unwrap_err();
+
+    assert_eq!(FailureClass::of(&error), FailureClass::InvalidUpdate);
+    assert_eq!(
+        error.downcast_ref::<UpdateFailure>(),
+        Some(&UpdateFailure { index: 0, domain })
+    );
+
+    let id = app.prove(root).await.unwrap();
+    let update = match wait_job(&app, id).await {
+        JobStatus::Failed { update, .. } => update.unwrap(),
+        s => panic!("unexpected job status {s:?}"),
+    };
+
+    assert_eq!(update.index, 0);
+    assert_eq!(update.domain, hex::encode(domain));
+    assert!(!update.reason.is_empty());
+    assert_eq!(prover.calls(), calls);
+}
+
+/// A prover that records the deferred proofs of its last inner proof.
+#[derive(Default, Clone)]
+struct RecordingProver {
+    prover: MockProver,
+    recursive: Arc<Mutex<Vec<DeferredProof>>>,
+}
+
+impl Prover for RecordingProver {
+    fn get_sp1_proof(
+        &self,
+        circuit: Hash,
+        proof_type: ProofType,
+        input: &[u8],
+        recursive: &[DeferredProof],
+        elf: &'static [u8],
+    ) -> anyhow::Result<Proof> {
+        if !matches!(proof_type, ProofType::Groth16) {
+            *self.recursive.lock().unwrap() = recursive.to_vec();
+        }
+
+        self.prover
+            .get_sp1_proof(circuit, proof_type, input, recursive, elf)
+    }
+}
+
+#[tokio::test]
+async fn compressed_light_clients_are_deferred() {
+    let coprocessor = MockCoprocessor::default();
+    let prover = RecordingProver::default();
+    let domain = Domain {
+        id: [0xcc; 32],
+        vk: format!("0x{}", "11".repeat(32)),
+        system: ProofSystem::Sp1Compressed,
+    };
+    let app = app_with(
+        &coprocessor,
+        &prover,
+        Options {
+            domains: vec![domain.clone()],
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    let payload = CompressedPayload {
+        proof: Proof::new(vec![1; 8], vec![2; 4]),
+        vk: vec![3; 4],
+    };
+
+    coprocessor.push_with_payload(domain.id, 1, payload.pack_to_vec());
+    coprocessor.push([0xee; 32], 2);
+
+    let state = app.update_to_latest().await.unwrap().unwrap();
+    let recursive = prover.recursive.lock().unwrap().clone();
+
+    // the previous inner proof, then the light-client proof of the elected block
+    assert_eq!(recursive.len(), 2);
+    assert_eq!(recursive[1].proof, payload.proof);
+    assert_eq!(recursive[1].vk, payload.vk);
+    assert_eq!(state.output().unwrap().blocks[0].id, domain.id);
+
+    // malformed payloads are rejected before the prover is called
+    coprocessor.push_with_payload(domain.id, 3, Vec::new());
+
+    let calls = prover.prover.calls();
+    let error = app.update_to_latest().await.unwrap_err();
+
+    assert_eq!(prover.prover.calls(), calls);
+    assert_eq!(FailureClass::of(&error), FailureClass::InvalidUpdate);
+    assert_eq!(
+        error.downcast_ref::<UpdateFailure>(),
+        Some(&UpdateFailure {
+            index: 0,
+            domain: domain.id
+        })
+    );
+}
@@ -0,0 +1,124 @@
+use std::{fs, io::Write as _, path::PathBuf};
+
+use valence_coprocessor::{HistoricalUpdate, Proof};
+use valence_coprocessor_domain_prover::{State, StateMetadata};
+use valence_coprocessor_domain_prover_service::{FileStorage, Storage, STORAGE_VERSION};
+
+fn state(uuid: u8) -> State {
+    State {
+        update: HistoricalUpdate {
+            uuid: [uuid; 16],
+            root: [uuid; 32],
+            ..Default::default()
+        },
+        proof: Proof::new(vec![uuid; 8], vec![uuid; 4]),
+        wrapper: Proof::new(vec![uuid; 8], vec![uuid; 4]),
+        metadata: StateMetadata {
+            proved_at: uuid as u64,
+            wrapped_at: uuid as u64,
+            ..Default::default()
+        },
+    }
+}
+
+fn path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("{name}-{}.bin", std::process::id()));
+
+    fs::remove_file(&path).ok();
+
+    path
+}
+
+#[test]
+fn states_roundtrip() {
+    let path = path("roundtrip");
+    let storage = FileStorage::open(&path).unwrap();
+
+    assert!(storage.load().unwrap().is_empty());
+
+    storage.persist(&state(1)).unwrap();
+    storage.persist(&state(2)).unwrap();
+
+    assert_eq!(storage.load().unwrap(), vec![state(1), state(2)]);
+
+    storage.flush(&[state(2)]).unwrap();
+    storage.persist(&state(3)).unwrap();
+
+    // reopening keeps the log
+    let storage = FileStorage::open(&path).unwrap();
+
+    assert_eq!(storage.load().unwrap(), vec![state(2), state(3)]);
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn truncated_records_are_discarded() {
+    let path = path("truncated");
+    let storage = FileStorage::open(&path).unwrap();
+
+    storage.persist(&state(1)).unwrap();
+    storage.persist(&state(2)).unwrap();
+
+    let len = fs::metadata(&path).unwrap().len();
+    let file = fs::OpenOptions::new().write(true).open(&path).unwrap();
+
+    file.set_len(len - 3).unwrap();
+
+    assert_eq!(storage.load().unwrap(), vec![state(1)]);
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn corrupt_lengths_are_bounded() {
+    let path = path("corrupt");
+    let storage = FileStorage::open(&path).unwrap();
+
+    storage.persist(&state(1)).unwrap();
+
+    fs::OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .unwrap()
+        .write_all(&u32::MAX.to_le_bytes())
+        .unwrap();
+
+    assert_eq!(storage.load().unwrap(), vec![state(1)]);
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn unknown_versions_are_rejected() {
+    let path = path("version");
+
+    fs::write(&path, [STORAGE_VERSION + 1]).unwrap();
+
+    let storage = FileStorage::open(&path).unwrap();
+
+    assert!(storage.load().is_err());
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn compaction_is_requested_periodically() {
+    let path = path("compaction");
+    let storage = FileStorage::open(&path).unwrap().with_compaction(2);
+
+    storage.persist(&state(1)).unwrap();
+
+    assert!(!storage.should_compact());
+
+    storage.persist(&state(2)).unwrap();
+
+    assert!(storage.should_compact());
+
+    storage.flush(&[state(2)]).unwrap();
+
+    assert!(!storage.should_compact());
+    assert_eq!(storage.load().unwrap(), vec![state(2)]);
+
+    fs::remove_file(&path).unwrap();
+}
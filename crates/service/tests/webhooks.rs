@@ -0,0 +1,140 @@
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use poem::{
+    handler, http::StatusCode, listener::TcpAcceptor, web::Data, Body, EndpointExt as _, Request,
+    Route, Server,
+};
+use serde_json::Value;
+use valence_coprocessor_domain_prover_service::{App, Notification, Webhooks, SIGNATURE_HEADER};
+
+/// The requests received by the stub, with their signature header.
+type Received = Arc<Mutex<Vec<(Option<String>, Vec<u8>)>>>;
+
+#[derive(Clone)]
+struct Stub {
+    received: Received,
+    failures: Arc<Mutex<usize>>,
+}
+
+#[handler]
+async fn hook(req: &Request, body: Body, Data(stub): Data<&Stub>) -> StatusCode {
+    let signature = req.header(SIGNATURE_HEADER).map(ToString::to_string);
+    let body = body.into_vec().await.unwrap();
+
+    stub.received.lock().unwrap().push((signature, body));
+
+    let mut failures = stub.failures.lock().unwrap();
+
+    if *failures > 0 {
+        *failures -= 1;
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    StatusCode::OK
+}
+
+/// Spawns a webhook stub failing the first `failures` requests.
+async fn stub(failures: usize) -> (String, Received) {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr: SocketAddr = listener.local_addr().unwrap();
+    let received = Received::default();
+    let stub = Stub {
+        received: received.clone(),
+        failures: Arc::new(Mutex::new(failures)),
+    };
+
+    let app = Route::new().at("/hook", poem::post(hook)).data(stub);
+    let acceptor = TcpAcceptor::from_tokio(listener).unwrap();
+
+    tokio::spawn(Server::new_with_acceptor(acceptor).run(app));
+
+    (format!("http://{addr}/hook"), received)
+}
+
+fn notification() -> Notification {
+    Notification::PublishRejected {
+        root: hex::encode([0xaa; 32]),
+        uuid: hex::encode([0xbb; 16]),
+    }
+}
+
+#[tokio::test]
+async fn notifications_are_signed() {
+    let (url, received) = stub(0).await;
+    let webhooks = Webhooks::new(vec![url]).with_secret("secret");
+
+    assert_eq!(webhooks.send(&notification()).await, 0);
+
+    let received = received.lock().unwrap();
+    let (signature, body) = &received[0];
+    let body_json: Value = serde_json::from_slice(body).unwrap();
+
+    assert_eq!(received.len(), 1);
+    assert_eq!(body_json["event"], "publish_rejected");
+    assert_eq!(
+        signature.as_deref(),
+        Some(Webhooks::sign("secret", body).unwrap().as_str())
+    );
+}
+
+#[tokio::test]
+async fn failed_deliveries_are_retried() {
+    let (url, received) = stub(2).await;
+    let webhooks = Webhooks::new(vec![url]).with_retries(2, Duration::from_millis(1));
+
+    assert_eq!(webhooks.send(&notification()).await, 0);
+    assert_eq!(received.lock().unwrap().len(), 3);
+    assert!(received.lock().unwrap()[0].0.is_none());
+}
+
+#[tokio::test]
+async fn undelivered_notifications_are_dead_lettered() {
+    let (url, received) = stub(usize::MAX).await;
+    let path = std::env::temp_dir().join(format!("dead-letter-{}.jsonl", std::process::id()));
+    let webhooks = Webhooks::new(vec![url.clone()])
+        .with_retries(1, Duration::from_millis(1))
+        .with_dead_letter(&path);
+
+    assert_eq!(webhooks.send(&notification()).await, 1);
+    assert_eq!(received.lock().unwrap().len(), 2);
+
+    let log = std::fs::read_to_string(&path).unwrap();
+    let record: Value = serde_json::from_str(log.lines().next().unwrap()).unwrap();
+
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(record["url"], url);
+    assert!(record["body"]
+        .as_str()
+        .unwrap()
+        .contains("publish_rejected"));
+}
+
+#[tokio::test]
+async fn failures_are_counted_by_the_health() {
+    let (url, received) = stub(0).await;
+    let webhooks = Webhooks::new(vec![url]).with_failure_threshold(2);
+    let app = App::new(10).with_webhooks(webhooks);
+    let interval = Duration::from_millis(1);
+    let failed = || -> anyhow::Result<()> { Err(anyhow::anyhow!("boom")) };
+
+    app.record_update(&failed(), interval);
+    app.record_update(&Ok(()), interval);
+    app.record_update(&failed(), interval);
+    app.record_update(&failed(), interval);
+    app.record_update(&failed(), interval);
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let received = received.lock().unwrap();
+    let body_json: Value = serde_json::from_slice(&received[0].1).unwrap();
+
+    assert_eq!(app.health().failures, 3);
+    assert_eq!(received.len(), 1);
+    assert_eq!(body_json["event"], "update_failed");
+    assert_eq!(body_json["failures"], 2);
+}
@@ -0,0 +1,77 @@
+use std::sync::{Arc, OnceLock};
+
+use sp1_sdk::{CpuProver, Prover as _, SP1ProofWithPublicValues, SP1VerifyingKey};
+use sp1_verifier::{Groth16Verifier, GROTH16_VK_BYTES};
+use valence_coprocessor::Proof;
+
+/// A verifier of the SP1 proofs of the inner and wrapper circuits, running natively.
+///
+/// The CPU prover required for the compressed proofs is set up on first use.
+#[derive(Clone)]
+pub struct Sp1Verifier {
+    cpu: Arc<OnceLock<CpuProver>>,
+    inner_vk: Arc<SP1VerifyingKey>,
+    wrapper_vk: String,
+}
+
+impl Sp1Verifier {
+    /// Creates a verifier from the cbor encoded inner vk and the wrapper vk bytes32.
+    pub fn new(inner_vk: &[u8], wrapper_vk: &str) -> anyhow::Result<Self> {
+        let inner_vk: SP1VerifyingKey = serde_cbor::from_slice(inner_vk)?;
+
+        Ok(Self {
+            cpu: Default::default(),
+            inner_vk: Arc::new(inner_vk),
+            wrapper_vk: wrapper_vk.trim().to_string(),
+        })
+    }
+
+    /// Verifies a compressed proof of the inner circuit, and that it commits to the public values
+    /// of the co-processor proof.
+    pub fn verify_inner(&self, proof: &Proof) -> anyhow::Result<()> {
+        let (proof, inputs) = proof.decode()?;
+        let proof: SP1ProofWithPublicValues = serde_cbor::from_slice(&proof)?;
+
+        anyhow::ensure!(
+            proof.public_values.as_slice() == inputs.as_slice(),
+            "inner proof public values mismatch"
+        );
+
+        self.cpu
+            .get_or_init(CpuProver::new)
+            .verify(&proof, &self.inner_vk)
+            .map_err(|e| anyhow::anyhow!("invalid inner proof: {e}"))
+    }
+
+    /// Verifies a Groth16 proof of the wrapper circuit.
+    pub fn verify_wrapper(&self, proof: &Proof) -> anyhow::Result<()> {
+        let (proof, inputs) = proof.decode()?;
+
+        Groth16Verifier::verify(&proof, &inputs, &self.wrapper_vk, &GROTH16_VK_BYTES)
+            .map_err(|e| anyhow::anyhow!("invalid wrapper proof: {e}"))
+    }
+}
+
+#[test]
+fn verify_inner_rejects_mismatched_public_values() {
+    use sp1_sdk::{SP1Proof, SP1PublicValues};
+
+    let verifier = Sp1Verifier::new(
+        include_bytes!("../../../elf/circuit-vk.bin"),
+        include_str!("../../../elf/wrapper-bytes32"),
+    )
+    .unwrap();
+
+    let proof = SP1ProofWithPublicValues {
+        proof: SP1Proof::Core(Vec::new()),
+        public_values: SP1PublicValues::from(&[1, 2, 3]),
+        sp1_version: String::new(),
+        tee_proof: None,
+    };
+    let proof = serde_cbor::to_vec(&proof).unwrap();
+    let error = verifier
+        .verify_inner(&Proof::new(proof, vec![4, 5, 6]))
+        .unwrap_err();
+
+    assert!(error.to_string().contains("public values mismatch"));
+}
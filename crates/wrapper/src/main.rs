@@ -10,8 +10,8 @@ pub fn main() {
     let vk = include_bytes!("../../../elf/circuit-vkh32.bin");
     let inputs = sp1_zkvm::io::read_vec();
 
-    let root = &inputs[..32];
-    let vk_p = &inputs[32..];
+    // the inner output is suffixed with its vk
+    let (output, vk_p) = inputs.split_at(inputs.len() - vk.len());
 
     assert_eq!(vk_p, vk);
 
@@ -20,5 +20,5 @@ pub fn main() {
 
     verify_sp1_proof(&vk, &digest);
 
-    sp1_zkvm::io::commit_slice(&root);
+    sp1_zkvm::io::commit_slice(output);
 }